@@ -6,23 +6,27 @@
 
 mod macros;
 
-#[path="cargo/_cargo.rs"] pub mod cargo;
-#[path="fs/_fs.rs"      ] pub mod fs;
-#[path="io/_io.rs"      ] pub mod io;
+#[path="cargo/_cargo.rs"    ] pub mod cargo;
+#[path="command/_command.rs"] pub mod command; pub use command::{Command, CommandError};
+#[path="fs/_fs.rs"          ] pub mod fs;
+#[path="io/_io.rs"          ] pub mod io;
 
 #[doc(hidden)] pub mod _log_impl; // macro implementation details
 
 pub mod cargo_about;
 pub mod cargo_web;
 mod command_ext;    pub use command_ext::CommandExt;
-mod command;        pub use command::Command;
 pub mod env;
 mod path_ext;       pub use path_ext::PathExt;
 pub mod path;
+mod progress;       pub use progress::Progress;
 pub mod rustc;
 pub mod rustup;     pub use rustup::Rustup;
 mod result_ext;     pub use result_ext::ResultExt;
+mod scoped;
+mod section;        pub use section::Section;
 mod version;        #[cfg(feature = "version")] pub use version::Version;
 pub mod vscode;
 pub mod wasm_bindgen;
 pub mod wasm_pack;
+mod which;          pub use which::which;
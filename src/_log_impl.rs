@@ -1,6 +1,14 @@
-use std::io;
-use std::path::Path;
-use std::sync::atomic::{AtomicUsize, Ordering::*};
+use crate::command::IoLine;
+use crate::scoped::{self, Registry};
+
+use std::ffi::OsString;
+use std::fmt::{self, Display};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering::*};
 
 
 
@@ -22,42 +30,966 @@ pub struct Stats {
 }
 
 impl Stats {
+    /// The number of [error!](crate::error)s and [warning!](crate::warning)s logged so far
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # use mmrbi::_log_impl::Stats;
+    /// warning!("a warning");
+    /// error!("an error");
+    /// error!("another error");
+    /// let stats = Stats::get();
+    /// assert_eq!((stats.errors, stats.warnings), (2, 1));
+    ///
+    /// Stats::summary(); // error: could not complete due to 2 previous errors; 1 warning emitted
+    /// ```
     pub fn get() -> Self {
         Self {
             errors:     ERRORS.load(Acquire),
             warnings:   WARNINGS.load(Acquire),
         }
     }
+
+    /// Print a cargo style summary of the errors and warnings logged so far, if any, such as:
+    /// `error: could not complete due to 2 previous errors; 1 warning emitted`
+    pub fn summary() {
+        let Stats { errors, warnings } = Self::get();
+        let warnings = match warnings {
+            0 => String::new(),
+            1 => "1 warning emitted".into(),
+            n => format!("{} warnings emitted", n),
+        };
+        let ctx = Context::new;
+        match errors {
+            0 if warnings.is_empty()    => {},
+            0                           => write_uncounted(ctx(Severity::Warning), format_args!("{}", warnings)),
+            n                           => write_uncounted(ctx(Severity::Error), format_args!(
+                "could not complete due to {} previous error{}{}{}",
+                n, if n == 1 { "" } else { "s" }, if warnings.is_empty() { "" } else { "; " }, warnings,
+            )),
+        }
+    }
+
+    /// If any errors were logged, print a [Stats::summary] and `exit(1)`
+    pub fn exit_if_errors() {
+        if Self::get().errors > 0 {
+            Self::summary();
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/// Whether or not the logging macros should emit ANSI color codes.  See [set_color].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorChoice {
+    /// Decide the same way cargo does: based on `CARGO_TERM_COLOR`, `NO_COLOR`, `CLICOLOR_FORCE`, and whether stderr is a terminal
+    Auto,
+
+    /// Always emit color codes
+    Always,
+
+    /// Never emit color codes
+    Never,
 }
 
+/// Override the environment based [ColorChoice::Auto] detection of whether or not the logging macros use color.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// # use mmrbi::_log_impl::{ColorChoice, set_color};
+/// set_color(ColorChoice::Never);
+/// warning!("plain text, even on a terminal");
+/// set_color(ColorChoice::Auto);
+/// ```
+pub fn set_color(choice: ColorChoice) {
+    COLOR.store(choice as u8, Relaxed);
+}
+
+/// Whether or not the logging macros are currently emitting color codes
+pub fn color() -> bool {
+    match COLOR.load(Relaxed) {
+        c if c == ColorChoice::Always as u8 => return true,
+        c if c == ColorChoice::Never  as u8 => return false,
+        _                                   => {},
+    }
+    auto_color(|name| std::env::var_os(name), io::stderr().is_terminal())
+}
+
+/// [ColorChoice::Auto]'s decision, given a lookup for environment variables, and whether or not stderr is a terminal
+fn auto_color(var: impl Fn(&str) -> Option<OsString>, terminal: bool) -> bool {
+    let var = |name| var(name).filter(|v| !v.is_empty());
+    match var("CARGO_TERM_COLOR").as_ref().and_then(|v| v.to_str()) {
+        Some("always")  => return true,
+        Some("never")   => return false,
+        _               => {},
+    }
+    if var("NO_COLOR").is_some() { return false }
+    if var("CLICOLOR_FORCE").is_some_and(|v| v != "0") { return true }
+    terminal && var("TERM").map_or(cfg!(windows), |term| term != "dumb")
+}
+
+static COLOR : AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+/// `code` if [color] is enabled, otherwise nothing
+fn sgr(color: bool, code: &str) -> &str { if color { code } else { "" } }
+
+
+
+/// How [error!](crate::error), [warning!](crate::warning), and [info!](crate::info) format diagnostics.  See [set_error_format].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorFormat {
+    /// Human readable text, in the same style as cargo or rustc (the default)
+    Human,
+
+    /// One JSON object per line, in the same format as `rustc --error-format=json`.
+    ///
+    /// [StderrSink] suppresses [status!](crate::status) and [header!](crate::header) output in this mode, so that stderr
+    /// contains nothing but JSON.
+    Json,
+}
+
+/// Override the `MMRBI_ERROR_FORMAT` environment variable (`human` or `json`) to select how diagnostics are formatted.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// # use mmrbi::_log_impl::{ErrorFormat, set_error_format};
+/// set_error_format(ErrorFormat::Json);
+/// warning!(at: "src/lib.rs", line: 2, col: 3, code: "W1", "unused thing");
+/// // {"$message_type":"diagnostic","message":"unused thing","code":{"code":"W1","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs",...
+/// set_error_format(ErrorFormat::Human);
+/// ```
+pub fn set_error_format(format: ErrorFormat) {
+    FORMAT.store(format as u8 + 1, Relaxed);
+}
+
+/// The [ErrorFormat] currently in use
+pub fn error_format() -> ErrorFormat {
+    match FORMAT.load(Relaxed) {
+        0 if std::env::var_os("MMRBI_ERROR_FORMAT").is_some_and(|f| f == "json") => ErrorFormat::Json,
+        0                                       => ErrorFormat::Human,
+        f if f == ErrorFormat::Json as u8 + 1   => ErrorFormat::Json,
+        _                                       => ErrorFormat::Human,
+    }
+}
 
+/// `0` if unset (fall back on `MMRBI_ERROR_FORMAT`), otherwise `ErrorFormat as u8 + 1`
+static FORMAT : AtomicU8 = AtomicU8::new(0);
+
+
+
+/// Where a diagnostic points to, and how to render it: see [error!](crate::error) for the equivalent macro syntax.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::_log_impl::{self, Context, Child, Severity};
+/// # use std::path::Path;
+/// let ctx = Context::new(Severity::Warning)
+///     .code("W1")
+///     .at(Path::new("Cargo.toml")).line(3).col(2).len(9)
+///     .note("workspaces are shared by every member")
+///     .child(Child::help("see the previous definition").at("Cargo.toml").line(1));
+/// _log_impl::write(ctx, format_args!("unexpected table"));
+/// ```
+#[derive(Clone)]
 pub struct Context<'c> {
     pub severity:   Severity,
     pub code:       &'c str,
     pub at:         Option<&'c Path>,
     pub line:       usize,
     pub col:        usize,
+    pub len:        usize,
+    pub end_col:    usize,
+    pub children:   Vec<Child>,
 }
 
+impl<'c> Context<'c> {
+    pub fn new(severity: Severity) -> Self {
+        Self { severity, code: "", at: None, line: 0, col: 0, len: 0, end_col: 0, children: Vec::new() }
+    }
 
+    pub fn code     (mut self, code:    &'c str ) -> Self { self.code       = code;     self }
+    pub fn at       (mut self, at:      &'c Path) -> Self { self.at         = Some(at); self }
+    pub fn line     (mut self, line:    usize   ) -> Self { self.line       = line;     self }
+    pub fn col      (mut self, col:     usize   ) -> Self { self.col        = col;      self }
+    pub fn len      (mut self, len:     usize   ) -> Self { self.len        = len;      self }
+    pub fn end_col  (mut self, end_col: usize   ) -> Self { self.end_col    = end_col;  self }
 
-pub fn write(ctx: Context, f: impl FnOnce(&mut std::io::StderrLock) -> io::Result<()>) {
-    let pre = match ctx.severity {
-        Severity::Error     => "\u{001B}[31;1merror",
-        Severity::Warning   => "\u{001B}[33;1mwarning",
-        Severity::Info      => "\u{001B}[36;1minfo",
-    };
+    /// Add a `= note: ...` sub-diagnostic
+    pub fn note(self, message: impl Display) -> Self { self.child(Child::note(message)) }
+
+    /// Add a `= help: ...` sub-diagnostic
+    pub fn help(self, message: impl Display) -> Self { self.child(Child::help(message)) }
+
+    /// Add a sub-diagnostic, which may have its own location
+    pub fn child(mut self, child: Child) -> Self { self.children.push(child); self }
+}
+
+
+
+/// The kind of a [Child] sub-diagnostic
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChildKind {
+    Note,
+    Help,
+}
+
+/// A `note:` or `help:` sub-diagnostic, rendered under the main message.  See [Context::child].
+#[derive(Clone, Debug)]
+pub struct Child {
+    pub kind:       ChildKind,
+    pub message:    String,
+    pub at:         Option<PathBuf>,
+    pub line:       usize,
+    pub col:        usize,
+}
+
+impl Child {
+    pub fn new(kind: ChildKind, message: impl Display) -> Self {
+        Self { kind, message: message.to_string(), at: None, line: 0, col: 0 }
+    }
 
-    use std::io::Write;
-    let stderr = std::io::stderr();
-    let mut stderr = stderr.lock();
-    let _ = write!(&mut stderr, "{}{}\u{001B}[37m:\u{001B}[0m ", pre, ctx.code);
-    let _ = f(&mut stderr);
+    pub fn note(message: impl Display) -> Self { Self::new(ChildKind::Note, message) }
+    pub fn help(message: impl Display) -> Self { Self::new(ChildKind::Help, message) }
 
-    if let Some(at) = ctx.at {
-        let _ = writeln!(&mut stderr, "  \u{001B}[36;1m-->\u{001B}[0m {}:{}:{}", at.display(), ctx.line, ctx.col);
+    pub fn at   (mut self, at:   impl Into<PathBuf>) -> Self { self.at   = Some(at.into()); self }
+    pub fn line (mut self, line: usize             ) -> Self { self.line = line;            self }
+    pub fn col  (mut self, col:  usize             ) -> Self { self.col  = col;             self }
+
+    fn name(&self) -> &'static str {
+        match self.kind {
+            ChildKind::Note => "note",
+            ChildKind::Help => "help",
+        }
     }
+
+    fn style(&self) -> &'static str {
+        match self.kind {
+            ChildKind::Note => "\u{001B}[32;1m",
+            ChildKind::Help => "\u{001B}[36;1m",
+        }
+    }
+
+    fn loc(&self) -> Loc<'_> { Loc { at: self.at.as_deref(), line: self.line, col: self.col, len: 0, end_col: 0 } }
 }
 
+
+
+/// A location within a source file
+struct Loc<'a> {
+    at:         Option<&'a Path>,
+    line:       usize,
+    col:        usize,
+    len:        usize,
+    end_col:    usize,
+}
+
+impl Loc<'_> {
+    /// The number of columns to underline, from `end_col` if set, otherwise `len` (at least `1`)
+    fn span_len(&self) -> usize {
+        if self.end_col > self.col { self.end_col - self.col } else { self.len.max(1) }
+    }
+
+    /// The exclusive end column of the span, or `0` if there's no column
+    fn span_end(&self) -> usize {
+        if self.col == 0 { 0 } else { self.col + self.span_len() }
+    }
+
+    /// Spaces as wide as the line number gutter
+    fn pad(&self) -> String { " ".repeat(self.line.to_string().len()) }
+}
+
+/// Source files read while rendering a single [Diagnostic], so that each is read at most once
+#[derive(Default)]
+struct Sources(RefCell<Vec<(PathBuf, Option<String>)>>);
+
+impl Sources {
+    /// The source code line `loc` points to, if readable
+    fn line(&self, loc: &Loc) -> Option<String> {
+        let at = loc.at.filter(|_| loc.line != 0)?;
+        self.with(at, |src| src.lines().nth(loc.line - 1).map(String::from))
+    }
+
+    /// The byte offsets of the start and (exclusive) end of `loc`'s span within its file, if readable
+    fn bytes(&self, loc: &Loc) -> Option<(usize, usize)> {
+        self.with(loc.at?, |src| {
+            let mut start = 0;
+            for _ in 1 .. loc.line { start += src[start..].find('\n')? + 1; }
+            let line = src[start..].split('\n').next().unwrap_or("");
+            let byte = |col: usize| start + line.char_indices().nth(col.max(1) - 1).map_or(line.len(), |(i, _)| i);
+            Some((byte(loc.col), byte(loc.span_end())))
+        })
+    }
+
+    fn with<R>(&self, at: &Path, f: impl FnOnce(&str) -> Option<R>) -> Option<R> {
+        let mut files = self.0.borrow_mut();
+        let i = match files.iter().position(|(path, _)| path == at) {
+            Some(i) => i,
+            None    => { files.push((at.into(), std::fs::read_to_string(at).ok())); files.len() - 1 },
+        };
+        f(files[i].1.as_deref()?)
+    }
+}
+
+
+
+pub fn write(ctx: Context, message: fmt::Arguments) {
+    match ctx.severity {
+        Severity::Error     => { ERRORS  .fetch_add(1, AcqRel); let _ = THREAD_ERRORS.try_with(|e| e.set(e.get() + 1)); },
+        Severity::Warning   => { WARNINGS.fetch_add(1, AcqRel); },
+        Severity::Info      => {},
+    }
+
+    write_uncounted(ctx, message);
+}
+
+fn write_uncounted(ctx: Context, message: fmt::Arguments) {
+    let diagnostic = Diagnostic::new(ctx, message);
+    with_sink(|sink| sink.diagnostic(&diagnostic));
+}
+
+/// Implementation of [status!](crate::status)
+pub fn status(verb: &dyn Display, message: fmt::Arguments) {
+    let (verb, message) = (verb.to_string(), message.to_string());
+    with_sink(|sink| sink.status(&verb, &message));
+}
+
+/// Implementation of [header!](crate::header)
+pub fn header(message: fmt::Arguments) {
+    let message = message.to_string();
+    with_sink(|sink| sink.header(&message));
+}
+
+/// The number of [error!](crate::error)s logged on the current thread so far, so [Section](crate::Section)s can tell if they failed
+pub(crate) fn thread_errors() -> usize { THREAD_ERRORS.try_with(Cell::get).unwrap_or(0) }
+
 static ERRORS   : AtomicUsize = AtomicUsize::new(0);
 static WARNINGS : AtomicUsize = AtomicUsize::new(0);
+thread_local! { static THREAD_ERRORS : Cell<usize> = const { Cell::new(0) }; }
+
+
+
+/// An owned, fully formatted [error!](crate::error), [warning!](crate::warning), or [info!](crate::info), as passed to a [LogSink].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub severity:   Severity,
+    pub code:       String,
+    pub path:       Option<PathBuf>,
+    pub line:       usize,
+    pub col:        usize,
+    pub len:        usize,
+    pub end_col:    usize,
+    pub children:   Vec<Child>,
+    pub message:    String,
+}
+
+impl Diagnostic {
+    pub fn new(ctx: Context, message: fmt::Arguments) -> Self {
+        Self {
+            severity:   ctx.severity,
+            code:       ctx.code.into(),
+            path:       ctx.at.map(PathBuf::from),
+            line:       ctx.line,
+            col:        ctx.col,
+            len:        ctx.len,
+            end_col:    ctx.end_col,
+            children:   ctx.children,
+            message:    message.to_string(),
+        }
+    }
+
+    fn loc(&self) -> Loc<'_> { Loc { at: self.path.as_deref(), line: self.line, col: self.col, len: self.len, end_col: self.end_col } }
+}
+
+
+
+/// Where diagnostics, [status!](crate::status), and [header!](crate::header) output goes.  See [sink_thread] and [sink_process].
+pub trait LogSink: Send + Sync {
+    fn diagnostic(&self, diagnostic: &Diagnostic);
+    fn status(&self, _verb: &str, _message: &str) {}
+    fn header(&self, _message: &str) {}
+
+    /// A line of output from a labeled command, such as a [Jobs](crate::command::Jobs) job
+    fn output(&self, _label: &str, _line: IoLine) {}
+
+    /// If this sink writes to stderr, where a [Progress](crate::Progress) bar can be redrawn in place.
+    /// Otherwise (the default), progress is reported to this sink with periodic [status!](crate::status) lines instead.
+    fn is_stderr(&self) -> bool { false }
+}
+
+/// Log to both `A` and `B`
+impl<A: LogSink, B: LogSink> LogSink for (A, B) {
+    fn diagnostic(&self, diagnostic: &Diagnostic)   { self.0.diagnostic(diagnostic); self.1.diagnostic(diagnostic); }
+    fn status(&self, verb: &str, message: &str)     { self.0.status(verb, message); self.1.status(verb, message); }
+    fn header(&self, message: &str)                 { self.0.header(message); self.1.header(message); }
+    fn output(&self, label: &str, line: IoLine)     { self.0.output(label, line); self.1.output(label, line); }
+    fn is_stderr(&self) -> bool                     { self.0.is_stderr() || self.1.is_stderr() }
+}
+
+/// Log to `sink` on the current thread until the returned guard is dropped.
+///
+/// Takes priority over any [sink_process] sinks.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// # use mmrbi::_log_impl::{self, CaptureSink, Severity};
+/// let capture = CaptureSink::new();
+/// {
+///     let _capturing = _log_impl::sink_thread(capture.clone());
+///     warning!(at: "Cargo.toml", line: 3, code: "W1", "a {}", "warning");
+///     status!("Compiling", "not a diagnostic");
+/// }
+///
+/// let diagnostics = capture.diagnostics();
+/// assert_eq!(diagnostics.len(), 1);
+/// assert_eq!(diagnostics[0].severity, Severity::Warning);
+/// assert_eq!(diagnostics[0].code, "W1");
+/// assert_eq!(diagnostics[0].path.as_deref(), Some(std::path::Path::new("Cargo.toml")));
+/// assert_eq!((diagnostics[0].line, diagnostics[0].col), (3, 0));
+/// assert_eq!(diagnostics[0].message, "a warning");
+/// assert_eq!(capture.lines(), ["   Compiling not a diagnostic"]);
+/// ```
+pub fn sink_thread(sink: impl LogSink + 'static) -> LogSinkGuard {
+    sink_thread_arc(Arc::new(sink))
+}
+
+/// Log to `sink` on every thread until the returned guard is dropped.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use mmrbi::*;
+/// # use mmrbi::_log_impl::{self, FileSink, StderrSink};
+/// let _logging = _log_impl::sink_process((StderrSink, FileSink::create("target/build.log").unwrap()));
+/// status!("Building", "foo");
+/// ```
+pub fn sink_process(sink: impl LogSink + 'static) -> LogSinkGuard {
+    LogSinkGuard { _guard: SINKS.push_process(Arc::new(sink)) }
+}
+
+pub(crate) fn sink_thread_arc(sink: Arc<dyn LogSink>) -> LogSinkGuard {
+    LogSinkGuard { _guard: SINKS.push_thread(sink) }
+}
+
+/// The innermost active sink for the current thread, if any (otherwise output goes to [StderrSink].)
+///
+/// This may be called after thread locals are destroyed, by [Section](crate::Section)'s timing summary at exit.
+pub(crate) fn active_sink() -> Option<Arc<dyn LogSink>> {
+    SINKS.active()
+}
+
+fn with_sink(f: impl FnOnce(&dyn LogSink)) {
+    match active_sink() {
+        Some(sink)  => f(&*sink),
+        None        => f(&StderrSink),
+    }
+}
+
+/// Stops logging to a sink when dropped.  See [sink_thread] and [sink_process].
+///
+/// Not [Send]: a [sink_thread] guard must be dropped on the thread that created it.
+#[must_use = "logging to the sink stops when the guard is dropped"]
+pub struct LogSinkGuard { _guard: scoped::Guard<Arc<dyn LogSink>> }
+
+thread_local! { static THREAD_SINKS : scoped::Stack<Arc<dyn LogSink>> = const { RefCell::new(Vec::new()) }; }
+static SINKS : Registry<Arc<dyn LogSink>> = Registry::new(&THREAD_SINKS);
+
+
+
+/// The default [LogSink]: writes to stderr in the [ErrorFormat] and [color] selected, as cargo or rustc would.
+///
+/// In [ErrorFormat::Human] mode, also emits GitHub Actions or Azure Pipelines annotations (to stderr) for errors and warnings
+/// when running under either, and routes errors and warnings through [cargo::script::out::warning](crate::cargo::script::out::warning)
+/// inside build scripts.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StderrSink;
+
+impl LogSink for StderrSink {
+    fn diagnostic(&self, d: &Diagnostic) {
+        crate::progress::interleave(|| self.write_diagnostic(d));
+    }
+
+    fn status(&self, verb: &str, message: &str) {
+        if error_format() == ErrorFormat::Json { return }
+        let c = color();
+        crate::progress::interleave(|| writeln!(std::io::stderr().lock(), "{}{: >12}{} {}", sgr(c, "\u{001B}[32;1m"), verb, sgr(c, "\u{001B}[0m"), message)).ok();
+    }
+
+    fn header(&self, message: &str) {
+        if error_format() == ErrorFormat::Json { return }
+        let c = color();
+        crate::progress::interleave(|| writeln!(std::io::stderr().lock(), "{}{}{}", sgr(c, "\u{001B}[30;102m"), message, sgr(c, "\u{001B}[0m"))).ok();
+    }
+
+    /// Written to stdout or stderr, matching the stream the line came from
+    fn output(&self, label: &str, line: IoLine) {
+        crate::progress::interleave(|| match line.is_stderr() {
+            false   => writeln!(std::io::stdout().lock(), "[{}] {}", label, line.as_str()),
+            true    => writeln!(std::io::stderr().lock(), "[{}] {}", label, line.as_str()),
+        }).ok();
+    }
+
+    fn is_stderr(&self) -> bool { true }
+}
+
+impl StderrSink {
+    fn write_diagnostic(&self, d: &Diagnostic) {
+        let (format, ci) = (error_format(), ci());
+        let stderr = &mut std::io::stderr().lock();
+        let _ = if format == ErrorFormat::Human && d.severity <= Severity::Warning && build_script() {
+            render_build_script(d, &mut std::io::stdout().lock()).and_then(|()| annotate(d, ci, stderr))
+        } else {
+            render(d, format, color(), ci, stderr)
+        };
+    }
+}
+
+/// Write `d` to `out` in `format`, followed by any `ci` annotations.  [ErrorFormat::Json] is written without annotations,
+/// so that the output is nothing but JSON.
+fn render(d: &Diagnostic, format: ErrorFormat, color: bool, ci: Ci, out: &mut impl Write) -> io::Result<()> {
+    match format {
+        ErrorFormat::Human  => { human(d, &Sources::default(), color, out)?; annotate(d, ci, out) },
+        ErrorFormat::Json   => json(d, &Sources::default(), out),
+    }
+}
+
+/// Write `d` to `out` as [cargo::script::out::warning](crate::cargo::script::out::warning)s: one `cargo:warning=` line
+/// per line of uncolored, [ErrorFormat::Human] output.
+fn render_build_script(d: &Diagnostic, out: &mut impl Write) -> io::Result<()> {
+    let mut rendered = Vec::new();
+    human(d, &Sources::default(), false, &mut rendered)?;
+    crate::cargo::script::out::write_warning(out, &String::from_utf8_lossy(&rendered))
+}
+
+/// CI annotations for errors and warnings, so they show up inline on pull requests.  Both runners parse these from stderr
+/// as well as stdout, and stdout might be a build script's instructions to cargo, or output that's being captured.
+fn annotate(d: &Diagnostic, ci: Ci, out: &mut impl Write) -> io::Result<()> {
+    if d.severity > Severity::Warning { return Ok(()) }
+    if ci.github    { github(d, out)?; }
+    if ci.azure     { azure (d, out)?; }
+    Ok(())
+}
+
+/// If we're running as a build script.  Cargo hides build scripts' stderr unless the build fails, so errors and warnings are
+/// routed through [cargo::script::out::warning](crate::cargo::script::out::warning) instead.
+///
+/// Processes spawned by build scripts inherit cargo's build script environment variables, but their stdout isn't read by
+/// cargo, so the executable must also be named like one of cargo's build scripts (`build-script-build`.)
+pub(crate) fn build_script() -> bool {
+    static BUILD_SCRIPT : OnceLock<bool> = OnceLock::new();
+    *BUILD_SCRIPT.get_or_init(|| build_script_exe(std::env::current_exe().ok().as_deref()) && crate::cargo::script::Env::get().is_ok())
+}
+
+/// If `exe` is named like one of cargo's build scripts
+fn build_script_exe(exe: Option<&Path>) -> bool {
+    exe.and_then(Path::file_stem).and_then(|stem| stem.to_str()).is_some_and(|stem| stem.starts_with("build-script-") || stem.starts_with("build_script_"))
+}
+
+/// Which CI systems (that [StderrSink] writes annotations for) we're running under
+#[derive(Clone, Copy)]
+struct Ci {
+    github: bool,
+    azure:  bool,
+}
+
+fn ci() -> Ci {
+    static CI : OnceLock<Ci> = OnceLock::new();
+    *CI.get_or_init(|| {
+        let var = |name| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+        Ci { github: var("GITHUB_ACTIONS"), azure: var("TF_BUILD") }
+    })
+}
+
+/// A [LogSink] that collects [Diagnostic]s and other output in memory, for testing.  See [sink_thread] for an example.
+#[derive(Clone, Default)]
+pub struct CaptureSink(Arc<Mutex<Captured>>);
+
+#[derive(Default)]
+struct Captured {
+    diagnostics:    Vec<Diagnostic>,
+    lines:          Vec<String>,
+}
+
+impl CaptureSink {
+    pub fn new() -> Self { Self::default() }
+
+    /// Every diagnostic logged so far, in order
+    pub fn diagnostics(&self) -> Vec<Diagnostic> { self.0.lock().unwrap().diagnostics.clone() }
+
+    /// Every [status!](crate::status), [header!](crate::header), and command output line logged so far, in order, as
+    /// uncolored text (e.g. `"   Compiling foo"`)
+    pub fn lines(&self) -> Vec<String> { self.0.lock().unwrap().lines.clone() }
+}
+
+impl LogSink for CaptureSink {
+    fn diagnostic(&self, diagnostic: &Diagnostic)   { self.0.lock().unwrap().diagnostics.push(diagnostic.clone()); }
+    fn status(&self, verb: &str, message: &str)     { self.0.lock().unwrap().lines.push(format!("{: >12} {}", verb, message)); }
+    fn header(&self, message: &str)                 { self.0.lock().unwrap().lines.push(message.into()); }
+    fn output(&self, label: &str, line: IoLine)     { self.0.lock().unwrap().lines.push(format!("[{}] {}", label, line.as_str())); }
+}
+
+/// A [LogSink] that writes plain, uncolored text to a file.  See [sink_process] for an example.
+pub struct FileSink(Mutex<File>);
+
+impl FileSink {
+    /// Create or truncate `path`, creating parent directories as needed
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) { std::fs::create_dir_all(dir)?; }
+        Ok(Self(Mutex::new(File::create(path)?)))
+    }
+}
+
+impl LogSink for FileSink {
+    fn diagnostic(&self, d: &Diagnostic)            { let _ = human(d, &Sources::default(), false, &mut *self.0.lock().unwrap()); }
+    fn status(&self, verb: &str, message: &str)     { let _ = writeln!(self.0.lock().unwrap(), "{: >12} {}", verb, message); }
+    fn header(&self, message: &str)                 { let _ = writeln!(self.0.lock().unwrap(), "{}", message); }
+    fn output(&self, label: &str, line: IoLine)     { let _ = writeln!(self.0.lock().unwrap(), "[{}] {}", label, line.as_str()); }
+}
+
+
+
+fn human(d: &Diagnostic, src: &Sources, c: bool, out: &mut impl Write) -> io::Result<()> {
+    let (style, name) = match d.severity {
+        Severity::Error     => ("\u{001B}[31;1m", "error"),
+        Severity::Warning   => ("\u{001B}[33;1m", "warning"),
+        Severity::Info      => ("\u{001B}[36;1m", "info"),
+    };
+
+    write!(out, "{}{}", sgr(c, style), name)?;
+    if !d.code.is_empty() { write!(out, "[{}]", d.code)?; }
+    writeln!(out, "{}:{} {}", sgr(c, "\u{001B}[37m"), sgr(c, "\u{001B}[0m"), d.message)?;
+    let snippet = human_loc(&d.loc(), src, style, c, out)?;
+
+    let (unlocated, located) : (Vec<&Child>, Vec<&Child>) = d.children.iter().partition(|child| child.at.is_none());
+    let (gutter, reset) = (sgr(c, "\u{001B}[36;1m"), sgr(c, "\u{001B}[0m"));
+    let pad = d.loc().pad();
+    if snippet && !unlocated.is_empty() { writeln!(out, "{} {}|{}", pad, gutter, reset)?; }
+    for child in unlocated {
+        // rustc style, e.g. `  = note: ...`, with subsequent lines aligned to the first
+        let indent = format!("\n{}   {}  ", pad, " ".repeat(child.name().len()));
+        writeln!(out, "{} {}={} {}{}:{} {}", pad, gutter, reset, sgr(c, "\u{001B}[1m"), child.name(), reset, child.message.replace('\n', &indent))?;
+    }
+    for child in located {
+        writeln!(out, "{}{}{}:{} {}", sgr(c, child.style()), child.name(), sgr(c, "\u{001B}[37m"), reset, child.message)?;
+        human_loc(&child.loc(), src, child.style(), c, out)?;
+    }
+    Ok(())
+}
+
+/// Write ` --> path:line:col`, and a source snippet if readable, returning if a snippet was written
+fn human_loc(loc: &Loc, src: &Sources, style: &str, c: bool, out: &mut impl Write) -> io::Result<bool> {
+    let Some(at) = loc.at else { return Ok(false) };
+    let (gutter, reset) = (sgr(c, "\u{001B}[36;1m"), sgr(c, "\u{001B}[0m"));
+    let pad = loc.pad();
+    writeln!(out, "{}{}-->{} {}:{}:{}", pad, gutter, reset, at.display(), loc.line, loc.col)?;
+
+    // rustc style snippet, e.g.:
+    //   |
+    // 2 |     let x = foo;
+    //   |             ^^^
+    let Some(src) = src.line(loc) else { return Ok(false) };
+    let width = |ch| if ch == '\t' { 4 } else { 1 };
+    writeln!(out, "{} {}|{}", pad, gutter, reset)?;
+    writeln!(out, "{}{} |{} {}", gutter, loc.line, reset, src.replace('\t', "    "))?;
+    if loc.col != 0 {
+        let indent  = src.chars().take(loc.col - 1).map(width).sum::<usize>();
+        let carets  = src.chars().skip(loc.col - 1).take(loc.span_len()).map(width).sum::<usize>().max(1);
+        writeln!(out, "{} {}|{} {}{}{}{}", pad, gutter, reset, " ".repeat(indent), sgr(c, style), "^".repeat(carets), reset)?;
+    }
+    Ok(true)
+}
+
+/// The message with sub-diagnostics appended as extra lines, for backends without structured sub-diagnostics
+fn flatten(d: &Diagnostic) -> String {
+    let mut flat = d.message.clone();
+    for child in d.children.iter() {
+        flat = format!("{}\n{}: {}", flat, child.name(), child.message);
+        if let Some(at) = child.at.as_ref() { flat = format!("{} ({}:{}:{})", flat, at.display(), child.line, child.col); }
+    }
+    flat
+}
+
+/// Write a GitHub Actions [workflow command](https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#setting-an-error-message) such as `::error file=src/lib.rs,line=2,col=3,title=E1::message`
+fn github(d: &Diagnostic, out: &mut impl Write) -> io::Result<()> {
+    let escape = |s: &str, property: bool| {
+        let s = s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+        if property { s.replace(':', "%3A").replace(',', "%2C") } else { s }
+    };
+
+    let mut properties = Vec::new();
+    if let Some(at) = d.path.as_ref()   { properties.push(format!("file={}", escape(&at.to_string_lossy(), true))); }
+    if d.line != 0                      { properties.push(format!("line={}", d.line)); }
+    if d.col != 0                       { properties.push(format!("col={}", d.col)); }
+    if !d.code.is_empty()               { properties.push(format!("title={}", escape(&d.code, true))); }
+
+    let command = if d.severity == Severity::Error { "error" } else { "warning" };
+    let space = if properties.is_empty() { "" } else { " " };
+    writeln!(out, "::{}{}{}::{}", command, space, properties.join(","), escape(&flatten(d), false))
+}
+
+/// Write an Azure Pipelines [logging command](https://learn.microsoft.com/en-us/azure/devops/pipelines/scripts/logging-commands#logissue-log-an-error-or-warning) such as `##vso[task.logissue type=error;sourcepath=src/lib.rs;linenumber=2;columnnumber=3;code=E1]message`
+fn azure(d: &Diagnostic, out: &mut impl Write) -> io::Result<()> {
+    let escape = |s: &str| s.replace('%', "%AZP25").replace(';', "%3B").replace('\r', "%0D").replace('\n', "%0A").replace(']', "%5D");
+
+    let ty = if d.severity == Severity::Error { "error" } else { "warning" };
+    write!(out, "##vso[task.logissue type={}", ty)?;
+    if let Some(at) = d.path.as_ref()   { write!(out, ";sourcepath={}", escape(&at.to_string_lossy()))?; }
+    if d.line != 0                      { write!(out, ";linenumber={}", d.line)?; }
+    if d.col != 0                       { write!(out, ";columnnumber={}", d.col)?; }
+    if !d.code.is_empty()               { write!(out, ";code={}", escape(&d.code))?; }
+    writeln!(out, "]{}", escape(&flatten(d)))
+}
+
+/// Write a diagnostic as a single line of JSON, with the same schema as `rustc --error-format=json`
+fn json(d: &Diagnostic, src: &Sources, out: &mut impl Write) -> io::Result<()> {
+    let level = match d.severity {
+        Severity::Error     => "error",
+        Severity::Warning   => "warning",
+        Severity::Info      => "note",
+    };
+
+    let mut rendered = Vec::new();
+    human(d, src, false, &mut rendered)?;
+
+    write!(out, "{{\"$message_type\":\"diagnostic\",\"message\":{},\"code\":", JsonStr(&d.message))?;
+    if d.code.is_empty() { write!(out, "null")?; } else { write!(out, "{{\"code\":{},\"explanation\":null}}", JsonStr(&d.code))?; }
+    write!(out, ",\"level\":\"{}\",\"spans\":[", level)?;
+    json_span(&d.loc(), src, out)?;
+    write!(out, "],\"children\":[")?;
+    for (i, child) in d.children.iter().enumerate() {
+        if i > 0 { write!(out, ",")?; }
+        write!(out, "{{\"message\":{},\"code\":null,\"level\":\"{}\",\"spans\":[", JsonStr(&child.message), child.name())?;
+        json_span(&child.loc(), src, out)?;
+        write!(out, "],\"children\":[],\"rendered\":null}}")?;
+    }
+    writeln!(out, "],\"rendered\":{}}}", JsonStr(&String::from_utf8_lossy(&rendered)))
+}
+
+/// Lines and columns are 1-based, so a missing line or column is treated as the start of the file or line.
+/// Byte offsets are left out if the file can't be read.
+fn json_span(loc: &Loc, src: &Sources, out: &mut impl Write) -> io::Result<()> {
+    let Some(at) = loc.at else { return Ok(()) };
+    let (line, col, end) = (loc.line.max(1), loc.col.max(1), loc.span_end().max(1));
+    write!(out, "{{\"file_name\":{},", JsonStr(&at.to_string_lossy()))?;
+    if let Some((start, end)) = src.bytes(loc) {
+        write!(out, "\"byte_start\":{},\"byte_end\":{},", start, end)?;
+    }
+    write!(out, "\"line_start\":{line},\"line_end\":{line},\"column_start\":{col},\"column_end\":{end},\"is_primary\":true,\"text\":[")?;
+    if let Some(src) = src.line(loc) {
+        write!(out, "{{\"text\":{},\"highlight_start\":{},\"highlight_end\":{}}}", JsonStr(&src), col, end)?;
+    }
+    write!(out, "],\"label\":null,\"suggested_replacement\":null,\"suggestion_applicability\":null,\"expansion\":null}}")
+}
+
+/// Formats as a quoted and escaped JSON string
+struct JsonStr<'s>(&'s str);
+
+impl Display for JsonStr<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use fmt::Write;
+        fmt.write_char('"')?;
+        for ch in self.0.chars() {
+            match ch {
+                '"'                 => fmt.write_str("\\\"")?,
+                '\\'                => fmt.write_str("\\\\")?,
+                '\n'                => fmt.write_str("\\n")?,
+                '\r'                => fmt.write_str("\\r")?,
+                '\t'                => fmt.write_str("\\t")?,
+                c if c < ' '        => write!(fmt, "\\u{:04x}", c as u32)?,
+                c                   => fmt.write_char(c)?,
+            }
+        }
+        fmt.write_char('"')
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn json() {
+        // N.B. src/lib.rs doesn't exist, so byte offsets are left out
+        let ctx = Context::new(Severity::Warning).code("W1").at(Path::new("src/lib.rs")).line(2).col(3);
+        let mut out = Vec::new();
+        super::json(&Diagnostic::new(ctx.clone(), format_args!("unused \"thing\"")), &Sources::default(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            r#"{"$message_type":"diagnostic","message":"unused \"thing\"","code":{"code":"W1","explanation":null},"level":"warning","spans":["#,
+            r#"{"file_name":"src/lib.rs","line_start":2,"line_end":2,"column_start":3,"column_end":4,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}"#,
+            r#"],"children":[],"rendered":"warning[W1]: unused \"thing\"\n --> src/lib.rs:2:3\n"}"#, "\n",
+        ));
+
+        let ctx = Context::new(Severity::Error);
+        let mut out = Vec::new();
+        super::json(&Diagnostic::new(ctx.clone(), format_args!("tab\there")), &Sources::default(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            r#"{"$message_type":"diagnostic","message":"tab\there","code":null,"level":"error","spans":[],"children":[],"rendered":"error: tab\there\n"}"#, "\n",
+        ));
+    }
+
+    #[test] fn json_span() {
+        let path = fixture("json_span.txt", "first\nsécond line\n");
+        let span = |ctx: Context| {
+            let mut out = Vec::new();
+            super::json_span(&Diagnostic::new(ctx, format_args!("")).loc(), &Sources::default(), &mut out).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            out[out.find(",").unwrap() .. out.find(",\"label\"").unwrap()].to_string()
+        };
+        let at = || Context::new(Severity::Error).at(&path);
+
+        assert_eq!(span(at().line(2).col(3).len(2)), r#","byte_start":9,"byte_end":11,"line_start":2,"line_end":2,"column_start":3,"column_end":5,"is_primary":true,"text":[{"text":"sécond line","highlight_start":3,"highlight_end":5}]"#);
+        assert_eq!(span(at().line(2)),               r#","byte_start":6,"byte_end":6,"line_start":2,"line_end":2,"column_start":1,"column_end":1,"is_primary":true,"text":[{"text":"sécond line","highlight_start":1,"highlight_end":1}]"#);
+        assert_eq!(span(at()),                       r#","byte_start":0,"byte_end":0,"line_start":1,"line_end":1,"column_start":1,"column_end":1,"is_primary":true,"text":[]"#);
+    }
+
+    #[test] fn color_precedence() {
+        let auto = |vars: &[(&str, &str)], terminal| auto_color(|name| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.into()), terminal);
+
+        // TTY/TERM
+        assert!( auto(&[("TERM", "xterm-256color")], true));
+        assert!(!auto(&[("TERM", "xterm-256color")], false));
+        assert!(!auto(&[("TERM", "dumb")], true));
+        assert_eq!(auto(&[], true), cfg!(windows));
+
+        // CLICOLOR_FORCE > TTY/TERM
+        assert!( auto(&[("CLICOLOR_FORCE", "1"), ("TERM", "dumb")], false));
+        assert!(!auto(&[("CLICOLOR_FORCE", "0")], false));
+
+        // NO_COLOR > CLICOLOR_FORCE
+        assert!(!auto(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1"), ("TERM", "xterm-256color")], true));
+        assert!( auto(&[("NO_COLOR", ""), ("TERM", "xterm-256color")], true)); // empty = unset
+
+        // CARGO_TERM_COLOR > NO_COLOR
+        assert!( auto(&[("CARGO_TERM_COLOR", "always"), ("NO_COLOR", "1")], false));
+        assert!(!auto(&[("CARGO_TERM_COLOR", "never"), ("CLICOLOR_FORCE", "1"), ("TERM", "xterm-256color")], true));
+        assert!( auto(&[("CARGO_TERM_COLOR", "auto"), ("CLICOLOR_FORCE", "1")], false));
+    }
+
+    /// Write `contents` to `target/mmrbi-test-fixtures/{name}`, returning the relative path
+    fn fixture(name: &str, contents: &str) -> PathBuf {
+        let path = Path::new("target/mmrbi-test-fixtures").join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test] fn snippet() {
+        let path = fixture("snippet.toml", "[package]\nname = \"foo\"\n[workspace]\n");
+        let ctx = Context::new(Severity::Error).code("E1").at(&path).line(3).col(2).end_col(11);
+        let mut out = Vec::new();
+        human(&Diagnostic::new(ctx.clone(), format_args!("bad table")), &Sources::default(), false, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("\
+error[E1]: bad table
+ --> {}:3:2
+  |
+3 | [workspace]
+  |  ^^^^^^^^^
+", path.display()));
+
+        let ctx = Context::new(Severity::Warning).at(&path).line(3);
+        let mut out = Vec::new();
+        human(&Diagnostic::new(ctx.clone(), format_args!("no column")), &Sources::default(), false, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("warning: no column\n --> {}:3:0\n  |\n3 | [workspace]\n", path.display()));
+    }
+
+    #[test] fn children() {
+        let path = fixture("children.toml", "# fixture\n\n[workspace]\n");
+        let ctx = Context::new(Severity::Error).code("E1").at(&path).line(3).col(2).len(9)
+            .note("first line\nsecond line")
+            .help("try this")
+            .child(Child::note("defined here").at(&path).line(1).col(3));
+
+        let mut out = Vec::new();
+        human(&Diagnostic::new(ctx.clone(), format_args!("bad table")), &Sources::default(), false, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("\
+error[E1]: bad table
+ --> {path}:3:2
+  |
+3 | [workspace]
+  |  ^^^^^^^^^
+  |
+  = note: first line
+          second line
+  = help: try this
+note: defined here
+ --> {path}:1:3
+  |
+1 | # fixture
+  |   ^
+", path = path.display()));
+
+        let mut out = Vec::new();
+        super::json(&Diagnostic::new(ctx.clone(), format_args!("bad table")), &Sources::default(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(r#""children":[{"message":"first line\nsecond line","code":null,"level":"note","spans":[],"children":[],"rendered":null},{"message":"try this","#), "{}", out);
+        assert!(out.contains(&format!(r##"{{"message":"defined here","code":null,"level":"note","spans":[{{"file_name":{},"byte_start":2,"byte_end":3,"line_start":1,"line_end":1,"column_start":3,"column_end":4,"is_primary":true,"text":[{{"text":"# fixture","##, JsonStr(&path.to_string_lossy()))), "{}", out);
+
+        let ctx = Context::new(Severity::Error).code("E1").at(Path::new("Cargo.toml")).line(3).col(2)
+            .note("first line\nsecond line")
+            .help("try this")
+            .child(Child::note("defined here").at("Cargo.toml").line(1).col(3));
+        let mut out = Vec::new();
+        github(&Diagnostic::new(ctx.clone(), format_args!("bad table")), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "::error file=Cargo.toml,line=3,col=2,title=E1::bad table%0Anote: first line%0Asecond line%0Ahelp: try this%0Anote: defined here (Cargo.toml:1:3)\n");
+    }
+
+    #[test] fn ci() {
+        let ctx = Context::new(Severity::Error).code("E1").at(Path::new("src/a,b.rs")).line(2).col(3);
+        let (mut gh, mut az) = (Vec::new(), Vec::new());
+        github(&Diagnostic::new(ctx.clone(), format_args!("100% broken\n[see above]; sorry")), &mut gh).unwrap();
+        azure(&Diagnostic::new(ctx.clone(), format_args!("100% broken\n[see above]; sorry")), &mut az).unwrap();
+        assert_eq!(String::from_utf8(gh).unwrap(), "::error file=src/a%2Cb.rs,line=2,col=3,title=E1::100%25 broken%0A[see above]; sorry\n");
+        assert_eq!(String::from_utf8(az).unwrap(), "##vso[task.logissue type=error;sourcepath=src/a,b.rs;linenumber=2;columnnumber=3;code=E1]100%AZP25 broken%0A[see above%5D%3B sorry\n");
+
+        let ctx = Context::new(Severity::Warning);
+        let (mut gh, mut az) = (Vec::new(), Vec::new());
+        github(&Diagnostic::new(ctx.clone(), format_args!("a warning")), &mut gh).unwrap();
+        azure(&Diagnostic::new(ctx.clone(), format_args!("a warning")), &mut az).unwrap();
+        assert_eq!(String::from_utf8(gh).unwrap(), "::warning::a warning\n");
+        assert_eq!(String::from_utf8(az).unwrap(), "##vso[task.logissue type=warning]a warning\n");
+    }
+
+    #[test] fn build_script_warnings() {
+        let path = fixture("build_script.toml", "[package]\nname = \"foo\"\n");
+        let ctx = Context::new(Severity::Warning).at(&path).line(2).col(8).len(5).note("names must be unique");
+        let mut out = Vec::new();
+        render_build_script(&Diagnostic::new(ctx, format_args!("duplicate name")), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("\
+cargo:warning=warning: duplicate name
+cargo:warning= --> {path}:2:8
+cargo:warning=  |
+cargo:warning=2 | name = \"foo\"
+cargo:warning=  |        ^^^^^
+cargo:warning=  |
+cargo:warning=  = note: names must be unique
+", path = path.display()));
+
+        assert!( build_script_exe(Some(Path::new("target/debug/build/foo-0123456789abcdef/build-script-build"))));
+        assert!(!build_script_exe(Some(Path::new("target/debug/foo"))));
+        assert!(!build_script_exe(None));
+    }
+
+    #[test] fn annotations() {
+        let d = Diagnostic::new(Context::new(Severity::Error).code("E1"), format_args!("oh no"));
+        let ci = Ci { github: true, azure: true };
+
+        let mut out = Vec::new();
+        render(&d, ErrorFormat::Human, false, ci, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "error[E1]: oh no\n::error title=E1::oh no\n##vso[task.logissue type=error;code=E1]oh no\n");
+
+        let mut out = Vec::new();
+        render(&d, ErrorFormat::Json, false, ci, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 1, "{}", out);
+        assert!(out.starts_with(r#"{"$message_type":"diagnostic","message":"oh no","#), "{}", out);
+    }
+}
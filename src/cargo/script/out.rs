@@ -1,6 +1,7 @@
 //! [Outputs of the Build Script](https://doc.rust-lang.org/cargo/reference/build-scripts.html#outputs-of-the-build-script)
 
 use std::ffi::OsStr;
+use std::io::{self, Write};
 use std::path::Path;
 
 
@@ -68,9 +69,15 @@ pub fn rustc_cdylib_link_arg(flag: impl AsRef<str>) {
 /// [`cargo:warning=MESSAGE`](https://doc.rust-lang.org/cargo/reference/build-scripts.html#cargo-warning)
 /// — Displays a warning on the terminal.
 pub fn warning(message: impl AsRef<str>) {
-    for line in message.as_ref().lines() {
-        println!("cargo:warning={}", line.trim_end());
+    let _ = write_warning(&mut io::stdout().lock(), message.as_ref());
+}
+
+/// [warning], written to `out` instead of stdout
+pub(crate) fn write_warning(out: &mut impl Write, message: &str) -> io::Result<()> {
+    for line in message.lines() {
+        writeln!(out, "cargo:warning={}", line.trim_end())?;
     }
+    Ok(())
 }
 
 /// [`cargo:KEY=VALUE`](https://doc.rust-lang.org/cargo/reference/build-scripts.html#the-links-manifest-key)
@@ -12,8 +12,12 @@ use std::io;
 ///
 /// ```rust
 /// # use mmrbi::cargo_about;
+/// # let mocks = mmrbi::command::Mocks::new();
+/// # mocks.on("cargo", ["about", "--version"]).stdout("cargo-about 0.2.3\n");
+/// # let _mocking = mocks.mock_thread();
 /// let v = cargo_about::version().unwrap();
 /// assert_eq!(v.tool_name, "cargo-about");
+/// assert_eq!(v.version.to_string(), "0.2.3");
 /// ```
 #[cfg(feature = "version")]
 #[cfg_attr(doc_cfg, doc(cfg(feature = "version")))]
@@ -36,5 +40,5 @@ pub fn install_at_least(requested: &str) -> io::Result<()> {
     if let Ok(installed) = version() {
         if requested >= installed.version { return Ok(()); }
     }
-    Command::new("cargo").arg("install").arg("--version").arg(format!("^{}", requested)).arg("cargo-about").status0()
+    Ok(Command::new("cargo").arg("install").arg("--version").arg(format!("^{}", requested)).arg("cargo-about").status0()?)
 }
@@ -0,0 +1,580 @@
+//! [Command] and related types
+
+mod error;  pub use error::{CommandError, CommandErrorKind}; pub(crate) use error::StderrTail;
+mod jobs;   pub use jobs::{Jobs, JobsError};
+mod lines;  pub use lines::{IoLine, IoLineBuf, OutputLines};
+pub(crate) mod exec;   use exec::Pipe;
+mod mock;   pub use mock::{Mocks, Mock, MockGuard};
+mod pipeline; pub use pipeline::{Pipeline, PipelineError};
+mod quote;
+mod record; pub use record::{Recorder, RecordGuard};
+mod retry;  pub use retry::RetryPolicy;
+mod script; pub use script::Shell;
+mod serialize;
+mod tee;
+
+use std::collections::*;
+use std::fmt::{self, Display, Debug, Formatter};
+use std::ffi::*;
+use std::io::{self, Write};
+use std::path::*;
+use std::process::{Child, ExitStatus, Output, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+
+/// A [Clone](https://doc.rust-lang.org/std/clone/trait.Clone.html)able, [Display](https://doc.rust-lang.org/std/fmt/trait.Display.html)able clone of [std::process::Command](https://doc.rust-lang.org/std/process/struct.Command.html)
+#[derive(Clone)]
+pub struct Command {
+    program:    OsString,
+    dir:        Option<PathBuf>,
+    args:       Vec<OsString>,
+    env_clear:  bool,
+    env:        BTreeMap<OsString, Option<OsString>>, // None = removed
+
+    stdin:      Option<Arc<dyn Fn() -> Stdio + Send + Sync>>,
+    stdout:     Option<Arc<dyn Fn() -> Stdio + Send + Sync>>,
+    stderr:     Option<Arc<dyn Fn() -> Stdio + Send + Sync>>,
+
+    stdin_data: Option<Arc<[u8]>>,
+    timeout:    Option<Duration>,
+    retry:      Option<RetryPolicy>,
+    log:        Option<PathBuf>,
+}
+
+impl Display for Command {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "`{}`", self.display_native())
+    }
+}
+
+impl Debug for Command {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "`{}`", self.display_native())?;
+        if let Some(dir) = self.dir.as_ref() {
+            write!(fmt, ", in `{}`", dir.display())?;
+        }
+        if self.env_clear {
+            write!(fmt, ", with env cleared")?;
+        }
+        if !self.env.is_empty() {
+            write!(fmt, ", with env = {{")?;
+            for (k, v) in self.env.iter() {
+                match v {
+                    Some(v) => write!(fmt, " {:?} = {:?},", k, v)?,
+                    None    => write!(fmt, " -{},", k.to_string_lossy())?,
+                }
+            }
+            write!(fmt, "}}")?;
+        }
+        if let Some(data) = self.stdin_data.as_ref() {
+            write!(fmt, ", with {} bytes of stdin", data.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl Command {
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        Self {
+            program:    program.as_ref().into(),
+            dir:        None,
+            args:       Default::default(),
+            env_clear:  false,
+            env:        Default::default(),
+
+            stdin:      None,
+            stdout:     None,
+            stderr:     None,
+
+            stdin_data: None,
+            timeout:    None,
+            retry:      None,
+            log:        None,
+        }
+    }
+
+    /// Parse a command line consisting of space separated args, optionally `"double quoted"` (with `\"` escapes.)
+    ///
+    /// See [Command::parse_posix] and [Command::parse_windows] for parsing the full syntax of a specific shell.
+    pub fn parse(command: impl AsRef<str>) -> Result<Self, String> {
+        let original = command.as_ref();
+        let mut args = Vec::new();
+
+        let mut s = original.chars().peekable();
+        'parse_next_arg: loop {
+            while s.peek() == Some(&' ') { let _space = s.next(); } // ignore leading whitespace
+            if s.peek().is_none() { break }
+
+            let mut arg = String::new();
+            if s.peek() == Some(&'\"') { // arg is quoted
+                let _quote = s.next();
+                while let Some(ch) = s.next() {
+                    if ch == '\\' && s.peek() == Some(&'\"') { // escaped interior quote
+                        let _esc_quote = s.next();
+                        arg.push('\"');
+                    } else if ch == '\"' { // ending quote
+                        args.push(arg);
+                        continue 'parse_next_arg;
+                    } else {
+                        arg.push(ch);
+                    }
+                }
+                return Err(format!("unable to parse `{}`: last quoted arg was never terminated", original));
+            } else { // arg is unquoted
+                while let Some(ch) = s.next() {
+                    if ch == ' ' { // ending space
+                        args.push(arg);
+                        continue 'parse_next_arg;
+                    } else if ch == '\"' {
+                        return Err(format!("unable to parse `{}`: unquoted arg has interior quotes", original));
+                    } else {
+                        arg.push(ch);
+                    }
+                }
+                args.push(arg);
+            }
+        }
+
+        Self::from_split(original, args)
+    }
+
+    /// Parse a POSIX `sh` style command line, supporting `'single quotes'`, `"double quotes"`, `\` escapes, and tab/newline separators.
+    ///
+    /// No expansion of `$VARS`, `~`, globs, etc. is performed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::Command;
+    /// let cmd = Command::parse_posix(r#"cargo build --features 'a b' --message-format="json" it\'s"#).unwrap();
+    /// assert_eq!(cmd.display_posix().to_string(), r#"cargo build --features 'a b' --message-format=json 'it'\''s'"#);
+    /// assert!(Command::parse_posix("echo 'unterminated").is_err());
+    /// ```
+    pub fn parse_posix(command: impl AsRef<str>) -> Result<Self, String> {
+        let original = command.as_ref();
+        Self::from_split(original, quote::split_posix(original)?)
+    }
+
+    /// Parse a Windows style command line, following the backslash and quoting rules of
+    /// [`CommandLineToArgvW`](https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-commandlinetoargvw).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::Command;
+    /// let cmd = Command::parse_windows(r#""C:\Program Files\foo.exe" "a b" a\\\"b "C:\dir\\""#).unwrap();
+    /// assert_eq!(cmd.display_windows().to_string(), r#""C:\Program Files\foo.exe" "a b" a\\\"b C:\dir\"#);
+    /// ```
+    pub fn parse_windows(command: impl AsRef<str>) -> Result<Self, String> {
+        let original = command.as_ref();
+        Self::from_split(original, quote::split_windows(original))
+    }
+
+    fn from_split(original: &str, args: Vec<String>) -> Result<Self, String> {
+        match &args[..] {
+            [ exe, args @ .. ] => {
+                let mut cmd = Command::new(exe);
+                cmd.args(args);
+                Ok(cmd)
+            },
+            [] => Err(format!("unable to parse `{}`: empty/blank string? no args were parsed", original)),
+        }
+    }
+
+    /// Render as a POSIX `sh` command line that [Command::parse_posix] will parse back into the same program and args.
+    pub fn display_posix(&self) -> impl Display + '_ {
+        CommandLine { cmd: self, exe: quote::write_posix, arg: quote::write_posix }
+    }
+
+    /// Render as a Windows command line that [Command::parse_windows] will parse back into the same program and args.
+    pub fn display_windows(&self) -> impl Display + '_ {
+        CommandLine { cmd: self, exe: quote::write_windows_exe, arg: quote::write_windows }
+    }
+
+    fn display_native(&self) -> impl Display + '_ {
+        if cfg!(windows) { CommandLine { cmd: self, exe: quote::write_windows_exe, arg: quote::write_windows } }
+        else             { CommandLine { cmd: self, exe: quote::write_posix,       arg: quote::write_posix   } }
+    }
+
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.args.push(arg.as_ref().into());
+        self
+    }
+
+    pub fn args<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(&mut self, args: I) -> &mut Self {
+        self.args.extend(args.into_iter().map(|a| a.as_ref().into()));
+        self
+    }
+
+    pub fn env(&mut self, key: impl AsRef<OsStr>, val: impl AsRef<OsStr>) -> &mut Self {
+        self.env.insert(key.as_ref().into(), Some(val.as_ref().into()));
+        self
+    }
+
+    pub fn envs<I: IntoIterator<Item = (K, V)>, K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, vars: I) -> &mut Self {
+        self.env.extend(vars.into_iter().map(|(k, v)| (k.as_ref().into(), Some(v.as_ref().into()))));
+        self
+    }
+
+    /// Remove `key` from the environment the process inherits, undoing any previous [Command::env] for `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// let mut cmd = Command::new("rustup");
+    /// cmd.env("RUSTUP_TOOLCHAIN", "stable").env_remove("RUSTUP_TOOLCHAIN");
+    /// assert_eq!(format!("{:?}", cmd), "`rustup`, with env = { -RUSTUP_TOOLCHAIN,}");
+    /// assert_eq!(cmd.to_command().get_envs().collect::<Vec<_>>(), [("RUSTUP_TOOLCHAIN".as_ref(), None)]);
+    /// ```
+    pub fn env_remove(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.env.insert(key.as_ref().into(), None);
+        self
+    }
+
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.env.clear();
+        self.env_clear = true;
+        self
+    }
+
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Create the process's stdin with `f` each time it's spawned.
+    ///
+    /// **Breaking:** earlier versions accepted any `Fn() -> Stdio`, but `f` must now be [Send] + [Sync], since [Command]s
+    /// are sent to worker threads by [Jobs], and kept by [CommandError]s, [Mocks], and [Recorder]s shared between threads.
+    /// Plain functions like [Stdio::null] still qualify, but closures capturing [Rc](std::rc::Rc) or [RefCell](std::cell::RefCell) state don't.
+    pub fn stdin(&mut self, f: impl Fn() -> Stdio + Send + Sync + 'static) -> &mut Self {
+        self.stdin = Some(Arc::new(f));
+        self.stdin_data = None;
+        self
+    }
+
+    /// Pipe `data` into the process's stdin, replacing any previous [Command::stdin] configuration.
+    ///
+    /// The data is written on a separate thread, so large inputs won't deadlock against unread output.
+    /// Stdin is closed once all of `data` has been written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # if cfg!(unix) {
+    /// let big = "0123456789abcdef\n".repeat(1 << 16);
+    /// assert_eq!(Command::new("cat").stdin_bytes(&big).stdout0().unwrap(), big);
+    /// # }
+    /// ```
+    pub fn stdin_bytes(&mut self, data: impl AsRef<[u8]>) -> &mut Self {
+        self.stdin = None;
+        self.stdin_data = Some(data.as_ref().into());
+        self
+    }
+
+    /// Pipe `data` into the process's stdin, replacing any previous [Command::stdin] configuration.
+    ///
+    /// See [Command::stdin_bytes] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # if cfg!(unix) {
+    /// let out = Command::new("sort").stdin_str("b\nc\na\n").stdout0().unwrap();
+    /// assert_eq!(out, "a\nb\nc\n");
+    /// # }
+    /// ```
+    pub fn stdin_str(&mut self, data: impl AsRef<str>) -> &mut Self {
+        self.stdin_bytes(data.as_ref())
+    }
+
+    /// Create the process's stdout with `f` each time it's spawned.  See [Command::stdin] for why `f` must be [Send] + [Sync].
+    pub fn stdout(&mut self, f: impl Fn() -> Stdio + Send + Sync + 'static) -> &mut Self {
+        self.stdout = Some(Arc::new(f));
+        self
+    }
+
+    /// Create the process's stderr with `f` each time it's spawned.  See [Command::stdin] for why `f` must be [Send] + [Sync].
+    pub fn stderr(&mut self, f: impl Fn() -> Stdio + Send + Sync + 'static) -> &mut Self {
+        self.stderr = Some(Arc::new(f));
+        self
+    }
+
+    /// Kill the process if it's still running after `timeout`, failing with [io::ErrorKind::TimedOut].
+    ///
+    /// Honored by [Command::status], [Command::output], and every [CommandExt](crate::CommandExt) method, but not by [Command::spawn].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # use std::time::Duration;
+    /// # if cfg!(unix) {
+    /// let err = Command::new("sleep").arg("10").timeout(Duration::from_millis(100)).status().unwrap_err();
+    /// assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    /// assert_eq!(err.to_string(), "`sleep 10` timed out after 100ms");
+    /// # }
+    /// ```
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Find the program to run the same way spawning this command would.
+    ///
+    /// Like [which](crate::which), but honors any `PATH` set via [Command::env], and resolves relative paths against [Command::current_dir].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// assert_eq!(Command::new("cargo").resolve(), which("cargo"));
+    /// assert_eq!(Command::new("cargo").env("PATH", "").resolve(), None);
+    /// assert_eq!(Command::new("nonexistent-mmrbi-command").resolve(), None);
+    /// ```
+    pub fn resolve(&self) -> Option<PathBuf> {
+        crate::which::search(&self.program, self.path_env().as_deref(), self.dir.as_deref())
+    }
+
+    /// The `PATH` the program will be searched for in
+    fn path_env(&self) -> Option<OsString> {
+        let is_path = |k: &OsString| if cfg!(windows) { k.eq_ignore_ascii_case("PATH") } else { k == "PATH" };
+        match self.env.iter().find(|(k, _)| is_path(k)) {
+            Some((_, path))             => path.clone(),
+            None if self.env_clear      => None,
+            None                        => std::env::var_os("PATH"),
+        }
+    }
+
+    /// Append timestamped, stream tagged lines of stdout/stderr to the file at `path`, between a header with this
+    /// command's [Debug] description and a footer with its exit status.
+    ///
+    /// Output that would otherwise be inherited is piped through the log and forwarded to this process's stdout/stderr.
+    /// Output that would otherwise be discarded or redirected elsewhere isn't logged.  [Command::spawn] ignores this.
+    /// If the log can't be opened, a [warning!](crate::warning) is logged and the command runs without it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # if cfg!(unix) {
+    /// # let path = std::env::temp_dir().join(format!("mmrbi-log-to-{}.log", std::process::id()));
+    /// Command::parse_posix("sh -c 'echo out; echo err >&2'").unwrap().log_to(&path).status0().unwrap();
+    /// let log = std::fs::read_to_string(&path).unwrap();
+    /// let log = log.lines().map(|line| line.split_once("] ").unwrap().1).collect::<Vec<_>>();
+    /// assert_eq!(log[0], "running: `sh -c 'echo out; echo err >&2'`");
+    /// assert!(log.contains(&"stdout: out"));
+    /// assert!(log.contains(&"stderr: err"));
+    /// assert_eq!(log[3], "finished: exit status: 0");
+    /// # std::fs::remove_file(&path).unwrap();
+    /// # }
+    /// ```
+    pub fn log_to(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.log = Some(path.as_ref().into());
+        self
+    }
+
+    pub fn to_command(&self) -> std::process::Command {
+        let mut c = std::process::Command::new(&self.program);
+        if let Some(dir) = self.dir.as_ref() { c.current_dir(dir); }
+        c.args(self.args.iter());
+        if self.env_clear { c.env_clear(); }
+        for (k, v) in self.env.iter() {
+            match v {
+                Some(v) => c.env(k, v),
+                None    => c.env_remove(k),
+            };
+        }
+        if let Some(stdin ) = self.stdin .as_ref() { c.stdin (stdin ()); }
+        if self.stdin_data.is_some()                { c.stdin (Stdio::piped()); }
+        if let Some(stdout) = self.stdout.as_ref() { c.stdout(stdout()); }
+        if let Some(stderr) = self.stderr.as_ref() { c.stderr(stderr()); }
+        c
+    }
+
+    /// Spawn the process without waiting for it to exit.
+    ///
+    /// Any [Command::stdin_bytes] data is written on a detached thread.  [Command::timeout] is ignored.
+    pub fn spawn(&self) -> io::Result<Child> {
+        let mut child = self.to_command().spawn().map_err(|err| CommandError::new(self.clone(), CommandErrorKind::Spawn(err)))?;
+        let _detached = exec::write_stdin(&mut child, self.stdin_data.as_ref());
+        Ok(child)
+    }
+
+    pub fn output(&self) -> io::Result<Output>      { Ok(self.run(self.to_output_command(), Pipe::Capture, Pipe::Capture)?) }
+    pub fn status(&self) -> io::Result<ExitStatus>  { Ok(self.run_status(None)?) }
+
+    /// Run with [std::process::Command::status]'s default of inheriting stdin/stdout/stderr
+    fn run_status(&self, tail: Option<&StderrTail>) -> Result<ExitStatus, CommandError> {
+        let mut c = self.to_command();
+        let (stdout, stderr) = self.tee_inherited(&mut c, self.stdout.is_none(), self.stderr.is_none(), tail);
+        Ok(self.run(c, stdout, stderr)?.status)
+    }
+
+    /// If [Command::log_to] is in use, pipe inherited stdout/stderr through the log, forwarding lines to our own stdout/stderr.
+    /// If `tail` is given, inherited stderr is also piped and forwarded, keeping its last few lines in `tail`.
+    fn tee_inherited(&self, c: &mut std::process::Command, stdout: bool, stderr: bool, tail: Option<&StderrTail>) -> (Pipe, Pipe) {
+        fn forward(mut to: impl Write + Send + 'static) -> Pipe {
+            Pipe::bytes(move |line| { let _ = to.write_all(line).and_then(|()| to.write_all(b"\n")); })
+        }
+        let log = self.log.is_some();
+        let stdout = if log && stdout { c.stdout(Stdio::piped()); forward(io::stdout()) } else { Pipe::Capture };
+        let stderr = if stderr && (log || tail.is_some()) { c.stderr(Stdio::piped()); forward(io::stderr()) } else { Pipe::Capture };
+        let stderr = match tail { Some(tail) => stderr.tail(tail), None => stderr };
+        (stdout, stderr)
+    }
+
+    /// Whether the `*0` methods should keep the tail of otherwise inherited stderr, for [RetryPolicy::retry_if], or to
+    /// recognize cargo's "no such command" errors
+    fn tails_stderr(&self) -> bool {
+        self.retry.is_some() || crate::which::subcommand_install_hint(&self.program, &self.args).is_some()
+    }
+
+    /// [Command::to_command], with [std::process::Command::output]'s defaults of a null stdin and piped stdout/stderr
+    fn to_output_command(&self) -> std::process::Command {
+        let mut c = self.to_command();
+        if self.stdin .is_none() && self.stdin_data.is_none() { c.stdin (Stdio::null()); }
+        if self.stdout.is_none() { c.stdout(Stdio::piped()); }
+        if self.stderr.is_none() { c.stderr(Stdio::piped()); }
+        c
+    }
+
+    fn run(&self, mut c: std::process::Command, mut stdout: Pipe, mut stderr: Pipe) -> Result<Output, CommandError> {
+        if let Some(output) = self.intercept(&mut stdout, &mut stderr) { return output }
+        let options = self.options();
+        let output = exec::run(&mut c, &options, stdout, stderr).map_err(|kind| CommandError::new(self.clone(), kind));
+        if let Some(tee) = options.tee { tee.footer(output.as_ref().map(|o| o.status)); }
+        output
+    }
+
+    /// The result of this command according to any active [Mocks] or [Recorder], instead of actually running it
+    fn intercept(&self, stdout: &mut Pipe, stderr: &mut Pipe) -> Option<Result<Output, CommandError>> {
+        let output = mock::intercept(self, stdout, stderr).or_else(|| record::intercept(self, stdout))?;
+        Some(output.map_err(|kind| CommandError::new(self.clone(), kind)))
+    }
+
+    fn options(&self) -> exec::Options {
+        exec::Options {
+            timeout:    self.timeout,
+            stdin:      self.stdin_data.clone(),
+            tee:        self.log.as_ref().and_then(|path| match tee::Tee::open(path, self) {
+                Ok(tee)     => Some(Arc::new(tee)),
+                Err(err)    => { crate::warning!("unable to log {} to `{}`: {}", self, path.display(), err); None },
+            }),
+        }
+    }
+
+    fn io_pipes(&self, on_out: Pipe, on_err: Pipe) -> Result<ExitStatus, CommandError> {
+        let mut c = self.to_command();
+        c.stdout(Stdio::piped()).stderr(Stdio::piped());
+        Ok(self.run(c, on_out, on_err)?.status)
+    }
+
+    fn io0_pipes(&self, on_out: Pipe, on_err: Pipe) -> Result<(), CommandError> {
+        let tail = StderrTail::default();
+        let status = self.io_pipes(on_out, on_err.tail(&tail))?;
+        CommandError::check(self, status, || tail.take())
+    }
+
+    fn run_lines(&self, mut c: std::process::Command, on_line: &mut dyn FnMut(IoLine)) -> Result<ExitStatus, CommandError> {
+        if let Some(output) = self.intercept(&mut Pipe::Capture, &mut Pipe::Capture) {
+            let output = output?;
+            for line in exec::split_lines(&output.stdout) { on_line(IoLine { line: &String::from_utf8_lossy(line), err: false }); }
+            for line in exec::split_lines(&output.stderr) { on_line(IoLine { line: &String::from_utf8_lossy(line), err: true  }); }
+            return Ok(output.status);
+        }
+        let options = self.options();
+        let status = exec::lines(&mut c, &options, on_line).map_err(|kind| CommandError::new(self.clone(), kind));
+        if let Some(tee) = options.tee { tee.footer(status.as_ref().copied()); }
+        status
+    }
+
+    fn stdout0_with(&self, inherit_stderr: bool) -> Result<String, CommandError> {
+        let mut c = self.to_output_command();
+        c.stderr(if inherit_stderr { Stdio::inherit() } else { Stdio::null() });
+        let tail = (inherit_stderr && self.tails_stderr()).then(StderrTail::default);
+        let (_, stderr) = self.tee_inherited(&mut c, false, inherit_stderr, tail.as_ref());
+        let output = self.run(c, Pipe::Capture, stderr)?;
+        match tail {
+            Some(tail)  => CommandError::check(self, output.status, || tail.take())?,
+            None        => CommandError::check_output(self, &output)?,
+        }
+        String::from_utf8(output.stdout).map_err(|_err| CommandError::new(self.clone(), CommandErrorKind::InvalidUtf8))
+    }
+}
+
+/// Copies the program, args, current directory, and environment of a [std::process::Command].
+/// Stdio configuration cannot be read back, and is therefore lost.
+impl From<&std::process::Command> for Command {
+    fn from(c: &std::process::Command) -> Self {
+        let mut cmd = Command::new(c.get_program());
+        cmd.args(c.get_args());
+        if let Some(dir) = c.get_current_dir() { cmd.current_dir(dir); }
+        for (k, v) in c.get_envs() {
+            match v {
+                Some(v) => cmd.env(k, v),
+                None    => cmd.env_remove(k),
+            };
+        }
+        cmd
+    }
+}
+
+struct CommandLine<'c> {
+    cmd:    &'c Command,
+    exe:    fn(&mut Formatter, &str) -> fmt::Result,
+    arg:    fn(&mut Formatter, &str) -> fmt::Result,
+}
+
+impl Display for CommandLine<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        (self.exe)(fmt, &self.cmd.program.to_string_lossy())?;
+        for arg in self.cmd.args.iter() {
+            fmt.write_str(" ")?;
+            (self.arg)(fmt, &arg.to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+impl crate::CommandExt for Command {
+    fn status0(&mut self) -> Result<(), CommandError> {
+        self.retrying(|| {
+            let tail = self.tails_stderr().then(StderrTail::default);
+            let status = self.run_status(tail.as_ref())?;
+            CommandError::check(self, status, || tail.map_or_else(Vec::new, |tail| tail.take()))
+        })
+    }
+
+    fn output0(&mut self) -> Result<Output, CommandError> {
+        self.retrying(|| {
+            let output = self.run(self.to_output_command(), Pipe::Capture, Pipe::Capture)?;
+            CommandError::check_output(self, &output)?;
+            Ok(output)
+        })
+    }
+
+    fn stdout0          (&mut self) -> Result<String, CommandError> { self.retrying(|| self.stdout0_with(true)) }
+    fn stdout0_no_stderr(&mut self) -> Result<String, CommandError> { self.retrying(|| self.stdout0_with(false)) }
+
+    fn io       (&mut self, on_out: impl Fn(&str ) + Send + Sync + 'static, on_err: impl Fn(&str ) + Send + Sync + 'static) -> io::Result<ExitStatus>     { Ok(self.io_pipes (Pipe::utf8 (on_out), Pipe::utf8 (on_err))?) }
+    fn io0      (&mut self, on_out: impl Fn(&str ) + Send + Sync + 'static, on_err: impl Fn(&str ) + Send + Sync + 'static) -> Result<(), CommandError>   { let (o, e) = (Arc::new(on_out), Arc::new(on_err)); self.retrying(|| { let (o, e) = (o.clone(), e.clone()); self.io0_pipes(Pipe::utf8 (move |l| o(l)), Pipe::utf8 (move |l| e(l))) }) }
+    fn io_lossy (&mut self, on_out: impl Fn(&str ) + Send + Sync + 'static, on_err: impl Fn(&str ) + Send + Sync + 'static) -> io::Result<ExitStatus>     { Ok(self.io_pipes (Pipe::lossy(on_out), Pipe::lossy(on_err))?) }
+    fn io0_lossy(&mut self, on_out: impl Fn(&str ) + Send + Sync + 'static, on_err: impl Fn(&str ) + Send + Sync + 'static) -> Result<(), CommandError>   { let (o, e) = (Arc::new(on_out), Arc::new(on_err)); self.retrying(|| { let (o, e) = (o.clone(), e.clone()); self.io0_pipes(Pipe::lossy(move |l| o(l)), Pipe::lossy(move |l| e(l))) }) }
+    fn io_bytes (&mut self, on_out: impl Fn(&[u8]) + Send + Sync + 'static, on_err: impl Fn(&[u8]) + Send + Sync + 'static) -> io::Result<ExitStatus>     { Ok(self.io_pipes (Pipe::bytes(on_out), Pipe::bytes(on_err))?) }
+    fn io0_bytes(&mut self, on_out: impl Fn(&[u8]) + Send + Sync + 'static, on_err: impl Fn(&[u8]) + Send + Sync + 'static) -> Result<(), CommandError>   { let (o, e) = (Arc::new(on_out), Arc::new(on_err)); self.retrying(|| { let (o, e) = (o.clone(), e.clone()); self.io0_pipes(Pipe::bytes(move |l| o(l)), Pipe::bytes(move |l| e(l))) }) }
+
+    fn io_lines(&mut self, mut on_line: impl FnMut(IoLine)) -> io::Result<ExitStatus> {
+        Ok(self.run_lines(self.to_command(), &mut on_line)?)
+    }
+
+    fn output_lines(&mut self) -> io::Result<OutputLines> {
+        let mut lines = Vec::new();
+        let status = self.run_lines(self.to_output_command(), &mut |line| lines.push(line.into()))?;
+        Ok(OutputLines { status, lines })
+    }
+}
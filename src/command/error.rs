@@ -0,0 +1,251 @@
+use super::Command;
+
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Output};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+
+
+/// The maximum number of trailing stderr lines kept by a [CommandError]
+const STDERR_TAIL : usize = 10;
+
+/// A [Command] that failed to spawn, to run to completion, or to exit successfully.
+///
+/// Converts into an [io::Error] for use with `?` in functions returning [io::Result].
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// # use mmrbi::command::CommandErrorKind;
+/// let err = Command::new("nonexistent-mmrbi-command").status0().unwrap_err();
+/// assert!(matches!(err.kind(), CommandErrorKind::NotFound { .. }));
+/// assert_eq!(err.command().to_string(), "`nonexistent-mmrbi-command`");
+/// assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::NotFound);
+///
+/// # if cfg!(unix) {
+/// let err = Command::new("wasm-pack").env("PATH", "/nonexistent/bin").status0().unwrap_err();
+/// assert_eq!(err.to_string(), "`wasm-pack` failed: program not found\n    searched:\n        /nonexistent/bin\n    hint: cargo install wasm-pack");
+///
+/// let err = Command::new("cargo").arg("about").env("PATH", "/nonexistent/bin").status0().unwrap_err();
+/// assert_eq!(err.to_string(), "`cargo about` failed: program not found\n    searched:\n        /nonexistent/bin\n    hint: install rust via https://rustup.rs/, then: cargo install cargo-about");
+///
+/// let err = Command::parse_posix("sh -c 'echo oh no >&2; exit 3'").unwrap().output0().unwrap_err();
+/// assert_eq!(err.code(), Some(3));
+/// assert_eq!(err.stderr(), ["oh no"]);
+///
+/// let err = Command::parse_posix("sh -c 'kill -9 $$'").unwrap().status0().unwrap_err();
+/// assert_eq!(err.signal(), Some(9));
+/// # }
+///
+/// // cargo reports missing subcommands with exit code 101
+/// let mocks = mmrbi::command::Mocks::new();
+/// mocks.on("cargo", ["web", "**"]).exit_code(101).stderr("error: no such command: `web`\n");
+/// let _mocking = mocks.mock_thread();
+/// let err = Command::new("cargo").args(&["web", "--version"]).stdout0().unwrap_err();
+/// assert!(matches!(err.kind(), CommandErrorKind::NotFound { .. }));
+/// assert_eq!(err.to_string(), "`cargo web --version` failed: program not found\n    hint: cargo install cargo-web\n    error: no such command: `web`");
+/// ```
+pub struct CommandError {
+    command:    Box<Command>,
+    kind:       CommandErrorKind,
+    stderr:     Vec<String>,
+    previous:   Vec<CommandError>,
+}
+
+/// Why a [CommandError] occurred
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CommandErrorKind {
+    /// The process could not be spawned (missing executable, permissions, ...)
+    Spawn(io::Error),
+
+    /// The program could not be found in any of the `searched` directories.
+    /// `hint` explains how to install well known tools.
+    NotFound { searched: Vec<PathBuf>, hint: Option<&'static str> },
+
+    /// Reading from, writing to, or waiting on the process failed
+    Io(io::Error),
+
+    /// The process was killed after running longer than [Command::timeout]
+    TimedOut(Duration),
+
+    /// The process exited with a non-zero exit code
+    ExitCode(i32),
+
+    /// The process was terminated by a signal (unix), which might not be known
+    Signal(Option<i32>),
+
+    /// The process's stdout was expected to be UTF-8, but wasn't
+    InvalidUtf8,
+}
+
+impl CommandError {
+    pub(crate) fn new(command: Command, kind: CommandErrorKind) -> Self {
+        let kind = match kind {
+            CommandErrorKind::Spawn(err) if err.kind() == io::ErrorKind::NotFound && err.raw_os_error().is_some() && command.resolve().is_none() => {
+                CommandErrorKind::NotFound { searched: crate::which::dirs(&command.program, command.path_env().as_deref()), hint: crate::which::install_hint(&command.program, &command.args) }
+            },
+            kind => kind,
+        };
+        Self { command: Box::new(command), kind, stderr: Vec::new(), previous: Vec::new() }
+    }
+
+    /// Returns an error if `status` isn't a successful exit status
+    ///
+    /// A well known cargo subcommand that cargo reports as missing is treated as [CommandErrorKind::NotFound].
+    pub(crate) fn check(command: &Command, status: ExitStatus, stderr: impl FnOnce() -> Vec<String>) -> Result<(), Self> {
+        let kind = match status.code() {
+            Some(0) => return Ok(()),
+            Some(n) => CommandErrorKind::ExitCode(n),
+            None    => CommandErrorKind::Signal(signal(status)),
+        };
+        let stderr = stderr();
+        let kind = match crate::which::subcommand_install_hint(&command.program, &command.args) {
+            Some((subcommand, hint)) if status.code() == Some(101) && stderr.iter().any(|line| line.contains(&format!("no such command: `{}`", subcommand))) => {
+                CommandErrorKind::NotFound { searched: Vec::new(), hint: Some(hint) }
+            },
+            _ => kind,
+        };
+        Err(Self { command: Box::new(command.clone()), kind, stderr, previous: Vec::new() })
+    }
+
+    /// Returns an error if `output.status` isn't a successful exit status, keeping the tail of `output.stderr`
+    pub(crate) fn check_output(command: &Command, output: &Output) -> Result<(), Self> {
+        Self::check(command, output.status, || {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let mut tail = stderr.lines().rev().take(STDERR_TAIL).map(String::from).collect::<Vec<_>>();
+            tail.reverse();
+            tail
+        })
+    }
+
+    /// Attach the failures of earlier attempts to run the same [Command]
+    pub(crate) fn with_previous_attempts(mut self, previous: Vec<CommandError>) -> Self {
+        self.previous = previous;
+        self
+    }
+
+    /// The [io::ErrorKind] this error converts into
+    pub(crate) fn io_kind(&self) -> io::ErrorKind {
+        match &self.kind {
+            CommandErrorKind::Spawn(err)    => err.kind(),
+            CommandErrorKind::Io(err)       => err.kind(),
+            CommandErrorKind::NotFound{..}  => io::ErrorKind::NotFound,
+            CommandErrorKind::TimedOut(_)   => io::ErrorKind::TimedOut,
+            CommandErrorKind::InvalidUtf8   => io::ErrorKind::InvalidData,
+            _                               => io::ErrorKind::Other,
+        }
+    }
+
+    /// The [Command] that failed
+    pub fn command(&self) -> &Command { &self.command }
+
+    /// Why the [Command] failed
+    pub fn kind(&self) -> &CommandErrorKind { &self.kind }
+
+    /// The exit code, if the process ran to completion with a non-zero exit code
+    pub fn code(&self) -> Option<i32> {
+        match self.kind {
+            CommandErrorKind::ExitCode(n)   => Some(n),
+            _                               => None,
+        }
+    }
+
+    /// The signal that terminated the process, if known
+    pub fn signal(&self) -> Option<i32> {
+        match self.kind {
+            CommandErrorKind::Signal(s)     => s,
+            _                               => None,
+        }
+    }
+
+    /// Up to the last 10 lines of stderr, if stderr was captured or passed through a callback
+    pub fn stderr(&self) -> &[String] { &self.stderr }
+
+    /// The failures of earlier attempts, if the [Command] was [retried](Command::retry)
+    pub fn previous_attempts(&self) -> &[CommandError] { &self.previous }
+}
+
+impl Debug for CommandError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("CommandError")
+            .field("command",   &self.command)
+            .field("kind",      &self.kind)
+            .field("stderr",    &self.stderr)
+            .field("previous",  &self.previous)
+            .finish()
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let cmd = &self.command;
+        match &self.kind {
+            CommandErrorKind::Spawn(err)        => write!(fmt, "{} failed: {}", cmd, err)?,
+            CommandErrorKind::NotFound { searched, hint } => {
+                write!(fmt, "{} failed: program not found", cmd)?;
+                if !searched.is_empty() { write!(fmt, "\n    searched:")?; }
+                for dir in searched.iter() { write!(fmt, "\n        {}", dir.display())?; }
+                if let Some(hint) = hint { write!(fmt, "\n    hint: {}", hint)?; }
+            },
+            CommandErrorKind::Io(err)           => write!(fmt, "{} failed: {}", cmd, err)?,
+            CommandErrorKind::TimedOut(after)   => write!(fmt, "{} timed out after {:?}", cmd, after)?,
+            CommandErrorKind::ExitCode(n)       => write!(fmt, "{} failed: exit code {}", cmd, n)?,
+            CommandErrorKind::Signal(Some(s))   => write!(fmt, "{} failed: terminated by signal {}", cmd, s)?,
+            CommandErrorKind::Signal(None)      => write!(fmt, "{} failed: terminated by signal", cmd)?,
+            CommandErrorKind::InvalidUtf8       => write!(fmt, "{} failed: stdout contained invalid unicode", cmd)?,
+        }
+        for line in self.stderr.iter() {
+            write!(fmt, "\n    {}", line)?;
+        }
+        let attempts = self.previous.len() + 1;
+        for (i, prev) in self.previous.iter().enumerate() {
+            write!(fmt, "\n    attempt {} of {}: {}", i+1, attempts, prev.to_string().replace('\n', "\n    "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            CommandErrorKind::Spawn(err)    => Some(err),
+            CommandErrorKind::Io(err)       => Some(err),
+            _                               => None,
+        }
+    }
+}
+
+impl From<CommandError> for io::Error {
+    fn from(err: CommandError) -> Self {
+        io::Error::new(err.io_kind(), err)
+    }
+}
+
+
+
+/// Keeps the last few lines of stderr passed through a line callback
+#[derive(Clone, Default)]
+pub(crate) struct StderrTail(Arc<Mutex<VecDeque<String>>>);
+
+impl StderrTail {
+    pub fn push(&self, line: &str) {
+        let mut tail = self.0.lock().unwrap();
+        if tail.len() == STDERR_TAIL { tail.pop_front(); }
+        tail.push_back(line.into());
+    }
+
+    pub fn take(&self) -> Vec<String> {
+        self.0.lock().unwrap().drain(..).collect()
+    }
+}
+
+
+
+#[cfg(unix)] fn signal(status: ExitStatus) -> Option<i32> { std::os::unix::process::ExitStatusExt::signal(&status) }
+#[cfg(not(unix))] fn signal(_status: ExitStatus) -> Option<i32> { None }
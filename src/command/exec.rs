@@ -0,0 +1,232 @@
+//! Shared spawn / read / wait logic for running a [std::process::Command] to completion.
+
+use super::{CommandErrorKind, IoLine, IoLineBuf, StderrTail, tee::Tee};
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ExitStatus, Output, Stdio};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+
+
+/// Extra behavior for [run] beyond what [std::process::Command] itself supports.
+#[derive(Default)]
+pub(crate) struct Options {
+    /// Kill the child if it hasn't exited after this long
+    pub timeout:    Option<Duration>,
+
+    /// Write this to the child's stdin (which should be piped) and then close it
+    pub stdin:      Option<Arc<[u8]>>,
+
+    /// Also append every line of piped stdout/stderr to this log
+    pub tee:        Option<Arc<Tee>>,
+}
+
+
+
+/// How to consume a child's stdout or stderr, if it was piped.
+pub(crate) enum Pipe {
+    /// Collect all output into [Output::stdout] / [Output::stderr]
+    Capture,
+
+    /// Invoke a callback per line of output (excluding the trailing `\n` or `\r\n`.)
+    ///
+    /// Once the callback returns an error, it's no longer invoked, but the pipe is still drained to avoid deadlocking the child.
+    Lines(OnLine),
+}
+
+type OnLine = Box<dyn FnMut(&[u8]) -> io::Result<()> + Send>;
+
+impl Pipe {
+    /// Invoke `on_line` per line, failing with [io::ErrorKind::InvalidData] if a line isn't valid UTF-8
+    pub fn utf8(mut on_line: impl FnMut(&str) + Send + 'static) -> Self {
+        Pipe::Lines(Box::new(move |line| {
+            let line = std::str::from_utf8(line).map_err(|_err| io::Error::new(io::ErrorKind::InvalidData, "output contained invalid unicode"))?;
+            on_line(line);
+            Ok(())
+        }))
+    }
+
+    /// Invoke `on_line` per line, replacing invalid UTF-8 with `U+FFFD`
+    pub fn lossy(mut on_line: impl FnMut(&str) + Send + 'static) -> Self {
+        Pipe::Lines(Box::new(move |line| { on_line(&String::from_utf8_lossy(line)); Ok(()) }))
+    }
+
+    /// Invoke `on_line` per line, without any UTF-8 validation
+    pub fn bytes(mut on_line: impl FnMut(&[u8]) + Send + 'static) -> Self {
+        Pipe::Lines(Box::new(move |line| { on_line(line); Ok(()) }))
+    }
+
+    /// Also keep the last few lines in `tail`
+    pub fn tail(self, tail: &StderrTail) -> Self {
+        match self {
+            Pipe::Capture => Pipe::Capture,
+            Pipe::Lines(mut on_line) => {
+                let tail = tail.clone();
+                Pipe::Lines(Box::new(move |line| { tail.push(&String::from_utf8_lossy(line)); on_line(line) }))
+            },
+        }
+    }
+}
+
+/// Spawn `cmd`, feed it any stdin data, consume any piped stdout/stderr, and wait for it to exit.
+///
+/// If `options.timeout` expires first, the child is killed and [CommandErrorKind::TimedOut] is returned.
+/// A non-zero exit status is *not* treated as an error.
+pub(crate) fn run(cmd: &mut std::process::Command, options: &Options, stdout: Pipe, stderr: Pipe) -> Result<Output, CommandErrorKind> {
+    run_with(cmd, options, stdout, stderr, |_deadline| {})
+}
+
+/// [run], but `during(deadline)` is invoked on the calling thread after spawning the child, and before waiting on it.
+fn run_with(cmd: &mut std::process::Command, options: &Options, stdout: Pipe, stderr: Pipe, during: impl FnOnce(Option<Instant>)) -> Result<Output, CommandErrorKind> {
+    let mut child = cmd.spawn().map_err(CommandErrorKind::Spawn)?;
+    let start = Instant::now();
+    let stdin  = write_stdin(&mut child, options.stdin.as_ref());
+    let stdout = child.stdout.take().map(|s| read(s, stdout, options.tee.clone().map(|t| (t, "stdout"))));
+    let stderr = child.stderr.take().map(|s| read(s, stderr, options.tee.clone().map(|t| (t, "stderr"))));
+    let deadline = options.timeout.map(|t| (start + t, t));
+    during(deadline.map(|(d, _)| d));
+    // N.B. on timeout, the threads are intentionally detached rather than joined: grandchildren may keep the pipes open
+    // indefinitely, even after the child itself has exited.
+    let status = wait(&mut child, start, options.timeout)?;
+    join(stdin,  deadline)?;
+    let stdout = join(stdout, deadline)?;
+    let stderr = join(stderr, deadline)?;
+    Ok(Output { status, stdout, stderr })
+}
+
+/// Run `cmd` with piped stdout/stderr, invoking `on_line` on the calling thread for each line of either, in order of arrival.
+pub(crate) fn lines(cmd: &mut std::process::Command, options: &Options, on_line: &mut dyn FnMut(IoLine)) -> Result<ExitStatus, CommandErrorKind> {
+    let (tx, rx) = mpsc::channel::<IoLineBuf>();
+    let err_tx = tx.clone();
+    let stdout = Pipe::utf8(move |line| { let _ = tx    .send(IoLineBuf { line: line.into(), err: false }); });
+    let stderr = Pipe::utf8(move |line| { let _ = err_tx.send(IoLineBuf { line: line.into(), err: true  }); });
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let output = run_with(cmd, options, stdout, stderr, |deadline| loop {
+        let line = match deadline {
+            None            => match rx.recv() { Ok(line) => line, Err(_disconnected) => break },
+            Some(deadline)  => match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) { Ok(line) => line, Err(_timeout_or_disconnected) => break },
+        };
+        on_line(line.as_io_line());
+    })?;
+    Ok(output.status)
+}
+
+/// Split `output` into lines the same way [Pipe::Lines] would
+pub(crate) fn split_lines(output: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let output = output.strip_suffix(b"\n").unwrap_or(output);
+    output.split(|b| *b == b'\n').map(|line| line.strip_suffix(b"\r").unwrap_or(line)).take(if output.is_empty() { 0 } else { usize::MAX })
+}
+
+/// Pass `output` to `pipe` the same way [run] would, returning whatever should be captured
+pub(crate) fn feed(pipe: &mut Pipe, output: Vec<u8>) -> io::Result<Vec<u8>> {
+    match pipe {
+        Pipe::Capture           => Ok(output),
+        Pipe::Lines(on_line)    => {
+            for line in split_lines(&output) { on_line(line)?; }
+            Ok(Vec::new())
+        },
+    }
+}
+
+/// Construct a fake [ExitStatus] for a process that exited with `code`
+#[cfg(unix)] pub(crate) fn exit_status(code: i32) -> ExitStatus { std::os::unix::process::ExitStatusExt::from_raw((code & 0xFF) << 8) }
+#[cfg(windows)] pub(crate) fn exit_status(code: i32) -> ExitStatus { std::os::windows::process::ExitStatusExt::from_raw(code as u32) }
+
+/// Write `data` to the child's stdin on another thread (so large inputs can't deadlock against unread output), then close it.
+pub(crate) fn write_stdin(child: &mut Child, data: Option<&Arc<[u8]>>) -> Option<JoinHandle<io::Result<()>>> {
+    let data = data?.clone();
+    let stdin = child.stdin.take()?;
+    Some(thread::spawn(move || write(stdin, &data)))
+}
+
+fn write(mut stdin: ChildStdin, data: &[u8]) -> io::Result<()> {
+    match stdin.write_all(data) {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()), // child exited or closed stdin without reading everything
+        other => other,
+    }
+}
+
+/// Wait for `child` (spawned at `start`) to exit, killing it if `timeout` expires first.
+fn wait(child: &mut Child, start: Instant, timeout: Option<Duration>) -> Result<ExitStatus, CommandErrorKind> {
+    let timeout = match timeout {
+        None            => return child.wait().map_err(CommandErrorKind::Io),
+        Some(timeout)   => timeout,
+    };
+
+    let deadline = start + timeout;
+    let mut poll = Duration::from_millis(1);
+    loop {
+        if let Some(status) = child.try_wait().map_err(CommandErrorKind::Io)? { return Ok(status) }
+        let now = Instant::now();
+        if now >= deadline {
+            let _ = child.kill(); // might've just exited on its own
+            let _ = child.wait();
+            return Err(CommandErrorKind::TimedOut(timeout));
+        }
+        thread::sleep(poll.min(deadline - now));
+        poll = (poll * 2).min(Duration::from_millis(50));
+    }
+}
+
+fn read(r: impl Read + Send + 'static, pipe: Pipe, tee: Option<(Arc<Tee>, &'static str)>) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || match (pipe, tee) {
+        (Pipe::Capture, None) => {
+            let mut r = r;
+            let mut buf = Vec::new();
+            r.read_to_end(&mut buf)?;
+            Ok(buf)
+        },
+        (Pipe::Capture, Some((tee, stream))) => {
+            let mut buf = Vec::new();
+            read_lines(r, |line| { tee.line(stream, line); Ok(()) }, |raw| buf.extend_from_slice(raw))?;
+            Ok(buf)
+        },
+        (Pipe::Lines(mut on_line), tee) => {
+            read_lines(r, |line| {
+                if let Some((tee, stream)) = tee.as_ref() { tee.line(stream, line); }
+                on_line(line)
+            }, |_raw| {})?;
+            Ok(Vec::new())
+        },
+    })
+}
+
+/// Invoke `on_line` for each line of `r` (excluding the trailing `\n` or `\r\n`), and `on_raw` with each line as read.
+///
+/// Once `on_line` returns an error, it's no longer invoked, but `r` is still drained to avoid deadlocking the child.
+fn read_lines(r: impl Read, mut on_line: impl FnMut(&[u8]) -> io::Result<()>, mut on_raw: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut r = BufReader::new(r);
+    let mut line = Vec::new();
+    let mut result = Ok(());
+    loop {
+        line.clear();
+        if r.read_until(b'\n', &mut line)? == 0 { break }
+        on_raw(&line);
+        if line.ends_with(b"\n") { line.pop(); }
+        if line.ends_with(b"\r") { line.pop(); }
+        if result.is_ok() { result = on_line(&line); }
+    }
+    result
+}
+
+/// Join `thread`, or detach it and fail with [CommandErrorKind::TimedOut] if it's still running at `deadline`.
+fn join<T: Default>(thread: Option<JoinHandle<io::Result<T>>>, deadline: Option<(Instant, Duration)>) -> Result<T, CommandErrorKind> {
+    let thread = match thread {
+        None            => return Ok(T::default()),
+        Some(thread)    => thread,
+    };
+
+    if let Some((deadline, timeout)) = deadline {
+        let mut poll = Duration::from_millis(1);
+        while !thread.is_finished() {
+            let now = Instant::now();
+            if now >= deadline { return Err(CommandErrorKind::TimedOut(timeout)) }
+            thread::sleep(poll.min(deadline - now));
+            poll = (poll * 2).min(Duration::from_millis(50));
+        }
+    }
+
+    thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)).map_err(CommandErrorKind::Io)
+}
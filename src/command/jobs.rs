@@ -0,0 +1,133 @@
+use super::{Command, CommandError, IoLine, Mocks, Recorder};
+use crate::CommandExt;
+use crate::_log_impl::{self, StderrSink};
+
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+
+
+/// Runs labeled [Command]s in parallel, prefixing each line of their output with their label.
+///
+/// Output goes through the active [LogSink](crate::_log_impl::LogSink), so it doesn't garble any [Progress](crate::Progress) bar.
+/// Progress is reported with [status!](crate::status).  Every command is run even if some fail, and all failures are
+/// reported together.  Any [Recorder] or [Mocks] active on the calling thread are also active for the commands.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// # use mmrbi::command::Jobs;
+/// # if cfg!(unix) {
+/// let mut jobs = Jobs::new();
+/// jobs.limit(2);
+/// jobs.add("a", Command::parse_posix("echo a").unwrap());
+/// jobs.add("b", Command::parse_posix("sh -c 'echo oh no >&2; exit 3'").unwrap());
+/// jobs.add("c", Command::parse_posix("false").unwrap());
+/// let err = jobs.run().unwrap_err();
+/// assert_eq!(err.failures().len(), 2);
+/// assert_eq!(err.failures()[0].0, "b");
+/// assert_eq!(err.failures()[0].1.code(), Some(3));
+/// assert_eq!(err.to_string(), "2 of 3 jobs failed:\nb: `sh -c 'echo oh no >&2; exit 3'` failed: exit code 3\n    oh no\nc: `false` failed: exit code 1");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Jobs {
+    jobs:   Vec<(String, Command)>,
+    limit:  Option<usize>,
+}
+
+impl Jobs {
+    /// Create a new, empty set of jobs.
+    pub fn new() -> Self { Self::default() }
+
+    /// Add a command to run, whose output lines will be prefixed with `[label]`
+    pub fn add(&mut self, label: impl Into<String>, command: Command) -> &mut Self {
+        self.jobs.push((label.into(), command));
+        self
+    }
+
+    /// Run at most `limit` commands at once.
+    ///
+    /// Defaults to `${NUM_JOBS}` if set (as it is for build scripts), or the number of CPUs otherwise.
+    pub fn limit(&mut self, limit: usize) -> &mut Self {
+        self.limit = Some(limit.max(1));
+        self
+    }
+
+    /// Run every command to completion, returning an error if any of them failed.
+    pub fn run(&self) -> Result<(), JobsError> {
+        let total   = self.jobs.len();
+        let limit   = self.limit.unwrap_or_else(default_limit).min(total);
+        let queue   = Mutex::new(self.jobs.iter().cloned().enumerate().collect::<VecDeque<_>>());
+        let results = Mutex::new(Vec::new());
+        let mocks   = Mocks::active();
+        let recorder= Recorder::active();
+        let sink    = _log_impl::active_sink();
+
+        thread::scope(|scope| for _ in 0 .. limit {
+            scope.spawn(|| {
+                let _mocking    = mocks.as_ref().map(Mocks::mock_thread);
+                let _recording  = recorder.as_ref().map(Recorder::record_thread);
+                let _logging    = sink.clone().map(_log_impl::sink_thread_arc);
+                loop {
+                    let (i, (label, mut command)) = match queue.lock().unwrap().pop_front() { Some(job) => job, None => break };
+                    // N.B. lines are read on other threads, which don't inherit this thread's sink
+                    let output = Arc::new((label.clone(), sink.clone()));
+                    let err_output = output.clone();
+                    let err = command.io0_lossy(
+                        move |line| output    .1.as_deref().unwrap_or(&StderrSink).output(&output    .0, IoLine { line, err: false }),
+                        move |line| err_output.1.as_deref().unwrap_or(&StderrSink).output(&err_output.0, IoLine { line, err: true  }),
+                    ).err();
+                    let mut results = results.lock().unwrap();
+                    crate::status!(if err.is_some() { "Failed" } else { "Finished" }, "{} ({}/{})", label, results.len() + 1, total);
+                    results.push((i, label, err));
+                }
+            });
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(i, _, _)| *i);
+        let failures = results.into_iter().filter_map(|(_, label, err)| Some((label, err?))).collect::<Vec<_>>();
+        if failures.is_empty() { Ok(()) } else { Err(JobsError { failures, total }) }
+    }
+}
+
+fn default_limit() -> usize {
+    std::env::var("NUM_JOBS").ok().and_then(|n| n.parse().ok()).filter(|n| *n > 0)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+}
+
+
+
+/// One or more [Jobs] failed
+#[derive(Debug)]
+pub struct JobsError {
+    failures:   Vec<(String, CommandError)>,
+    total:      usize,
+}
+
+impl JobsError {
+    /// The label and error of every job that failed, in the order they were added
+    pub fn failures(&self) -> &[(String, CommandError)] { &self.failures }
+}
+
+impl Display for JobsError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "{} of {} jobs failed:", self.failures.len(), self.total)?;
+        for (label, err) in self.failures.iter() {
+            write!(fmt, "\n{}: {}", label, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JobsError {}
+
+impl From<JobsError> for io::Error {
+    fn from(err: JobsError) -> Self { io::Error::other(err) }
+}
@@ -0,0 +1,70 @@
+use std::process::ExitStatus;
+
+
+
+/// A line of stdout or stderr, as passed to [CommandExt::io_lines](crate::CommandExt::io_lines)
+#[derive(Clone, Copy)]
+pub struct IoLine<'s> {
+    pub(crate) line:   &'s str,
+    pub(crate) err:    bool
+}
+
+impl IoLine<'_> {
+    pub fn as_str(&self) -> &str { self.line }
+    pub fn is_stdout(&self) -> bool { !self.err }
+    pub fn is_stderr(&self) -> bool {  self.err }
+}
+
+impl std::convert::AsRef<str>           for IoLine<'_>  { fn as_ref(&self) -> &str { self.line } }
+impl std::borrow::Borrow<str>           for IoLine<'_>  { fn borrow(&self) -> &str { self.line } }
+impl std::ops::Deref                    for IoLine<'_>  { fn deref(&self) -> &Self::Target { self.line } type Target = str;  }
+impl std::fmt::Debug                    for IoLine<'_>  { fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result { std::fmt::Debug::fmt(&self.line, fmt) } }
+impl std::fmt::Display                  for IoLine<'_>  { fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result { std::fmt::Display::fmt(&self.line, fmt) } }
+impl std::cmp::PartialEq< str>          for IoLine<'_>  { fn eq(&self, other: &str      ) -> bool { &**self == other } }
+impl std::cmp::PartialEq<&str>          for IoLine<'_>  { fn eq(&self, other: &&str     ) -> bool { &**self == *other } }
+impl std::cmp::PartialEq<IoLine<'_>>    for  str        { fn eq(&self, other: &IoLine   ) -> bool { self == &**other } }
+impl std::cmp::PartialEq<IoLine<'_>>    for &str        { fn eq(&self, other: &IoLine   ) -> bool { *self == &**other } }
+
+
+
+/// An owned [IoLine]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct IoLineBuf {
+    pub(crate) line:   String,
+    pub(crate) err:    bool
+}
+
+impl IoLineBuf {
+    pub fn as_io_line(&self) -> IoLine<'_> { IoLine { line: &self.line, err: self.err } }
+    pub fn as_str(&self) -> &str { &self.line }
+    pub fn is_stdout(&self) -> bool { !self.err }
+    pub fn is_stderr(&self) -> bool {  self.err }
+}
+
+impl From<IoLine<'_>>                   for IoLineBuf   { fn from(line: IoLine) -> Self { Self { line: line.line.into(), err: line.err } } }
+impl std::convert::AsRef<str>           for IoLineBuf   { fn as_ref(&self) -> &str { &self.line } }
+impl std::borrow::Borrow<str>           for IoLineBuf   { fn borrow(&self) -> &str { &self.line } }
+impl std::ops::Deref                    for IoLineBuf   { fn deref(&self) -> &Self::Target { &self.line } type Target = str;  }
+impl std::fmt::Debug                    for IoLineBuf   { fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result { std::fmt::Debug::fmt(&self.line, fmt) } }
+impl std::fmt::Display                  for IoLineBuf   { fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result { std::fmt::Display::fmt(&self.line, fmt) } }
+impl std::cmp::PartialEq< str>          for IoLineBuf   { fn eq(&self, other: &str      ) -> bool { &**self == other } }
+impl std::cmp::PartialEq<&str>          for IoLineBuf   { fn eq(&self, other: &&str     ) -> bool { &**self == *other } }
+impl std::cmp::PartialEq<IoLineBuf>     for  str        { fn eq(&self, other: &IoLineBuf) -> bool { self == &**other } }
+impl std::cmp::PartialEq<IoLineBuf>     for &str        { fn eq(&self, other: &IoLineBuf) -> bool { *self == &**other } }
+
+
+
+/// The result of [CommandExt::output_lines](crate::CommandExt::output_lines): stdout and stderr, interleaved in the order they were received.
+#[derive(Clone, Debug)]
+pub struct OutputLines {
+    pub status: ExitStatus,
+    pub lines:  Vec<IoLineBuf>,
+}
+
+impl OutputLines {
+    /// Only the lines of stdout
+    pub fn stdout(&self) -> impl Iterator<Item = &str> { self.lines.iter().filter(|l| l.is_stdout()).map(|l| l.as_str()) }
+
+    /// Only the lines of stderr
+    pub fn stderr(&self) -> impl Iterator<Item = &str> { self.lines.iter().filter(|l| l.is_stderr()).map(|l| l.as_str()) }
+}
@@ -0,0 +1,152 @@
+use super::{Command, CommandErrorKind, exec::{self, Pipe}};
+
+use crate::scoped::{self, Registry};
+
+use std::cell::RefCell;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::process::Output;
+use std::sync::{Arc, Mutex};
+
+
+
+/// Canned responses for [Command]s, so wrappers around external tools can be tested without those tools installed.
+///
+/// While active, [Command::status], [Command::output], and the [CommandExt](crate::CommandExt) methods look for a
+/// matching [Mocks::on] rule instead of spawning anything.  Commands without a matching rule fail to "spawn" with
+/// [io::ErrorKind::NotFound].  [Command::spawn] is unaffected.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// # use mmrbi::command::Mocks;
+/// let mocks = Mocks::new();
+/// mocks.on("rustup", ["--version"]).stdout("rustup 1.22.1 (b01adbbc3 2020-07-08)\n");
+/// mocks.on("rustup", ["target", "add", "*", "**"]).exit_code(1).stderr("error: toolchain 'nightly' is not installed\n");
+///
+/// let _mocking = mocks.mock_thread();
+/// assert_eq!(Command::new("rustup").arg("--version").stdout0().unwrap(), "rustup 1.22.1 (b01adbbc3 2020-07-08)\n");
+///
+/// let err = Command::parse_posix("rustup target add wasm32-unknown-unknown --toolchain nightly").unwrap().status0().unwrap_err();
+/// assert_eq!(err.code(), Some(1));
+///
+/// let err = Command::new("cargo").arg("--version").status0().unwrap_err();
+/// assert_eq!(std::io::Error::from(err).kind(), std::io::ErrorKind::NotFound);
+///
+/// assert_eq!(mocks.calls().len(), 3);
+/// ```
+#[derive(Clone, Default)]
+pub struct Mocks(Arc<Mutex<State>>);
+
+#[derive(Default)]
+struct State {
+    rules:  Vec<Rule>,
+    calls:  Vec<Command>,
+}
+
+struct Rule {
+    program:    OsString,
+    args:       Vec<String>,
+    exit_code:  i32,
+    stdout:     Vec<u8>,
+    stderr:     Vec<u8>,
+}
+
+impl Mocks {
+    /// Create a new, empty set of mocks.
+    pub fn new() -> Self { Self::default() }
+
+    /// Respond to `program` when run with args matching `args`, by default with exit code 0 and no output.
+    ///
+    /// In `args`, `"*"` matches any single arg, and `"**"` matches any number of args (including none.)
+    /// If multiple rules match, the most recently registered one wins.
+    pub fn on<I: IntoIterator<Item = S>, S: AsRef<str>>(&self, program: impl AsRef<OsStr>, args: I) -> Mock {
+        let mut state = self.0.lock().unwrap();
+        state.rules.push(Rule {
+            program:    program.as_ref().into(),
+            args:       args.into_iter().map(|a| a.as_ref().into()).collect(),
+            exit_code:  0,
+            stdout:     Vec::new(),
+            stderr:     Vec::new(),
+        });
+        Mock { mocks: self.clone(), rule: state.rules.len() - 1 }
+    }
+
+    /// Mock commands run on the current thread until the returned guard is dropped.
+    ///
+    /// Takes priority over any [Mocks::mock_process] mocks.
+    pub fn mock_thread(&self) -> MockGuard {
+        MockGuard { _guard: REGISTRY.push_thread(self.clone()) }
+    }
+
+    /// Mock commands run on any thread until the returned guard is dropped.
+    pub fn mock_process(&self) -> MockGuard {
+        MockGuard { _guard: REGISTRY.push_process(self.clone()) }
+    }
+
+    /// All commands run against these mocks so far (matched or not), in the order they were run.
+    pub fn calls(&self) -> Vec<Command> {
+        self.0.lock().unwrap().calls.clone()
+    }
+
+    /// The innermost active mocks for the current thread, if any.
+    pub(crate) fn active() -> Option<Self> {
+        REGISTRY.active()
+    }
+}
+
+/// A single [Mocks::on] rule, used to configure its response.
+pub struct Mock {
+    mocks:  Mocks,
+    rule:   usize,
+}
+
+impl Mock {
+    /// Set the exit code to respond with (defaults to `0`.)
+    pub fn exit_code(&self, code: i32) -> &Self { self.edit(|r| r.exit_code = code) }
+
+    /// Set the stdout to respond with (defaults to nothing.)
+    pub fn stdout(&self, stdout: impl AsRef<[u8]>) -> &Self { self.edit(|r| r.stdout = stdout.as_ref().into()) }
+
+    /// Set the stderr to respond with (defaults to nothing.)
+    pub fn stderr(&self, stderr: impl AsRef<[u8]>) -> &Self { self.edit(|r| r.stderr = stderr.as_ref().into()) }
+
+    fn edit(&self, f: impl FnOnce(&mut Rule)) -> &Self {
+        f(&mut self.mocks.0.lock().unwrap().rules[self.rule]);
+        self
+    }
+}
+
+/// Stops mocking when dropped.  See [Mocks::mock_thread] and [Mocks::mock_process].
+#[must_use = "mocking stops when the guard is dropped"]
+pub struct MockGuard { _guard: scoped::Guard<Mocks> }
+
+thread_local! { static THREAD : scoped::Stack<Mocks> = const { RefCell::new(Vec::new()) }; }
+static REGISTRY : Registry<Mocks> = Registry::new(&THREAD);
+
+
+
+/// If [Mocks] are active, return the matching canned response for `cmd` (or a not found error) instead of running it.
+pub(crate) fn intercept(cmd: &Command, stdout: &mut Pipe, stderr: &mut Pipe) -> Option<Result<Output, CommandErrorKind>> {
+    let mocks = Mocks::active()?;
+    let mut state = mocks.0.lock().unwrap();
+    state.calls.push(cmd.clone());
+    let args = cmd.args.iter().map(|a| a.to_string_lossy()).collect::<Vec<_>>();
+    let rule = match state.rules.iter().rev().find(|r| r.program == cmd.program && matches(&r.args, &args)) {
+        Some(rule)  => rule,
+        None        => return Some(Err(CommandErrorKind::Spawn(io::Error::new(io::ErrorKind::NotFound, "no mock registered for this command")))),
+    };
+    let (status, out, err) = (exec::exit_status(rule.exit_code), rule.stdout.clone(), rule.stderr.clone());
+    drop(state);
+    Some(exec::feed(stdout, out).and_then(|stdout| Ok(Output { status, stdout, stderr: exec::feed(stderr, err)? })).map_err(CommandErrorKind::Io))
+}
+
+fn matches(pattern: &[String], args: &[impl AsRef<str>]) -> bool {
+    match (pattern.split_first(), args.split_first()) {
+        (None, None)                                => true,
+        (Some((p, rest)), _) if p == "**"           => (0 ..= args.len()).any(|skip| matches(rest, &args[skip..])),
+        (Some((p, rest)), Some((a, args)))          => (p == "*" || p == a.as_ref()) && matches(rest, args),
+        _                                           => false,
+    }
+}
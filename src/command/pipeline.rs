@@ -0,0 +1,214 @@
+use super::{Command, CommandError, CommandErrorKind, Mocks, Recorder, exec::{self, Pipe}};
+
+use std::borrow::Borrow;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read};
+use std::process::{Child, Output, Stdio};
+use std::thread;
+
+
+
+/// The signal a process is killed by when writing to a closed pipe (13 on linux, macOS, and the BSDs)
+const SIGPIPE : i32 = 13;
+
+
+
+/// Two or more [Command]s, each with stdout piped into the stdin of the next.  See [Command::pipe].
+///
+/// All stages run concurrently.  Only the first stage's stdin configuration (including [Command::stdin_bytes]) is used,
+/// and only the last stage's stdout configuration is used.  Stderr is left as configured for every stage, and is
+/// drained if piped.  [Command::timeout], [Command::retry], and [Command::log_to] aren't supported for stages, and
+/// running a pipeline containing them fails without running anything.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// # if cfg!(unix) {
+/// let pipeline = Command::new("echo").arg("hello world").pipe(Command::new("tr").arg("a-z").arg("A-Z")).pipe(Command::new("rev"));
+/// assert_eq!(pipeline.to_string(), "`echo 'hello world' | tr a-z A-Z | rev`");
+/// assert_eq!(pipeline.stdout0().unwrap(), "DLROW OLLEH\n");
+///
+/// let err = Command::new("echo").pipe(Command::parse_posix("sh -c 'exit 3'").unwrap()).pipe(Command::new("cat")).status0().unwrap_err();
+/// assert_eq!(err.stage(), 1);
+/// assert_eq!(err.error().code(), Some(3));
+/// assert_eq!(err.to_string(), "`echo | sh -c 'exit 3' | cat` failed: `sh -c 'exit 3'` failed: exit code 3");
+///
+/// let err = Command::parse_posix("sh -c 'echo oops >&2; exit 1'").unwrap().stderr(std::process::Stdio::piped).pipe(Command::new("cat")).status0().unwrap_err();
+/// assert_eq!(err.error().stderr(), ["oops"]);
+///
+/// let err = Command::new("echo").timeout(std::time::Duration::from_secs(1)).pipe(Command::new("cat")).status0().unwrap_err();
+/// assert_eq!(err.stage(), 0);
+///
+/// // `yes` is killed by SIGPIPE once `head` exits, which isn't a failure
+/// assert_eq!(Command::new("yes").pipe(Command::parse_posix("head -1").unwrap()).stdout0().unwrap(), "y\n");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+    stages: Vec<Command>,
+}
+
+impl Command {
+    /// Pipe this command's stdout into the stdin of `next`.
+    pub fn pipe(&self, next: impl Borrow<Command>) -> Pipeline {
+        Pipeline { stages: vec![self.clone(), next.borrow().clone()] }
+    }
+}
+
+impl Pipeline {
+    /// Pipe the last stage's stdout into the stdin of `next`.
+    pub fn pipe(mut self, next: impl Borrow<Command>) -> Self {
+        self.stages.push(next.borrow().clone());
+        self
+    }
+
+    /// The [Command]s making up this pipeline, in order.
+    pub fn stages(&self) -> &[Command] { &self.stages }
+
+    /// Run the pipeline, returning an error if any stage fails.
+    pub fn status0(&self) -> Result<(), PipelineError> {
+        self.run(false).map(|_| ())
+    }
+
+    /// Run the pipeline, returning the last stage's stdout, or an error if any stage fails or stdout isn't valid UTF-8.
+    pub fn stdout0(&self) -> Result<String, PipelineError> {
+        let stdout = self.run(true)?;
+        String::from_utf8(stdout).map_err(|_err| self.error(self.stages.len()-1, CommandError::new(self.stages.last().unwrap().clone(), CommandErrorKind::InvalidUtf8)))
+    }
+
+    /// Run every stage, returning the last stage's stdout if `capture`.
+    fn run(&self, capture: bool) -> Result<Vec<u8>, PipelineError> {
+        for (i, stage) in self.stages.iter().enumerate() {
+            let unsupported = if stage.timeout.is_some() { "Command::timeout" } else if stage.retry.is_some() { "Command::retry" } else if stage.log.is_some() { "Command::log_to" } else { continue };
+            let err = io::Error::new(io::ErrorKind::InvalidInput, format!("{} isn't supported for pipeline stages", unsupported));
+            return Err(self.error(i, CommandError::new(stage.clone(), CommandErrorKind::Spawn(err))));
+        }
+        if Mocks::active().is_some() || Recorder::active().is_some() { return self.run_intercepted(capture) }
+
+        let last = self.stages.len()-1;
+        let mut children = Vec::<Child>::new();
+        let mut stdin = None;
+        let mut writer = None;
+        let mut stderrs = Vec::new();
+        for (i, stage) in self.stages.iter().enumerate() {
+            let mut c = stage.to_command();
+            if let Some(prev) = stdin.take() { c.stdin(Stdio::from(prev)); }
+            if i < last || capture { c.stdout(Stdio::piped()); }
+            let mut child = match c.spawn() {
+                Ok(child) => child,
+                Err(err) => {
+                    for mut child in children { let _ = child.kill(); let _ = child.wait(); }
+                    return Err(self.error(i, CommandError::new(stage.clone(), CommandErrorKind::Spawn(err))));
+                },
+            };
+            if i == 0 { writer = exec::write_stdin(&mut child, stage.stdin_data.as_ref()); }
+            if i < last { stdin = child.stdout.take(); }
+            stderrs.push(child.stderr.take().map(|mut s| thread::spawn(move || { let mut buf = Vec::new(); s.read_to_end(&mut buf).map(|_| buf) })));
+            children.push(child);
+        }
+
+        let mut stdout = Vec::new();
+        let read = match children[last].stdout.take() {
+            Some(mut s) => s.read_to_end(&mut stdout).map(|_| ()),
+            None        => Ok(()),
+        };
+        let statuses = children.iter_mut().map(|c| c.wait()).collect::<Vec<_>>();
+        let written = writer.map_or(Ok(Ok(())), |w| w.join());
+        let stderrs = stderrs.into_iter().map(|t| t.map_or(Ok(Vec::new()), |t| t.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))));
+        let outputs = statuses.into_iter().zip(stderrs).map(|(status, stderr)| Ok(Output { status: status.map_err(CommandErrorKind::Io)?, stdout: Vec::new(), stderr: stderr.map_err(CommandErrorKind::Io)? }));
+
+        self.check(outputs.collect())?;
+        read.map_err(|err| self.error(last, CommandError::new(self.stages[last].clone(), CommandErrorKind::Io(err))))?;
+        written.unwrap_or_else(|panic| std::panic::resume_unwind(panic)).map_err(|err| self.error(0, CommandError::new(self.stages[0].clone(), CommandErrorKind::Io(err))))?;
+        Ok(stdout)
+    }
+
+    /// Run every stage one after another through any active [Mocks] or [Recorder], feeding each stage's stdout to the next.
+    fn run_intercepted(&self, capture: bool) -> Result<Vec<u8>, PipelineError> {
+        let last = self.stages.len()-1;
+        let mut stdout = None::<Vec<u8>>;
+        let mut outputs = Vec::new();
+        for (i, stage) in self.stages.iter().enumerate() {
+            let mut stage = stage.clone();
+            if let Some(prev) = stdout.take() { stage.stdin_bytes(prev); }
+            let mut c = stage.to_command();
+            if i < last || capture { c.stdout(Stdio::piped()); }
+            let output = stage.run(c, Pipe::Capture, Pipe::Capture).map_err(|err| self.error(i, err))?;
+            stdout = Some(output.stdout.clone());
+            outputs.push(Ok(output));
+        }
+        self.check(outputs)?;
+        Ok(stdout.unwrap_or_default())
+    }
+
+    /// Fail with the last (rightmost) failing stage, if any, like bash's `set -o pipefail`.
+    ///
+    /// Unlike `pipefail`, a stage killed by `SIGPIPE` isn't a failure if every later stage succeeded, since that's how
+    /// pipelines like `yes | head -1` normally end.
+    fn check(&self, outputs: Vec<Result<Output, CommandErrorKind>>) -> Result<(), PipelineError> {
+        let last = self.stages.len()-1;
+        let results = self.stages.iter().zip(outputs).map(|(stage, output)| match output {
+            Ok(output)  => CommandError::check_output(stage, &output),
+            Err(kind)   => Err(CommandError::new(stage.clone(), kind)),
+        }).collect::<Vec<_>>();
+        for (i, result) in results.into_iter().enumerate().rev() {
+            match result {
+                Ok(())                                                  => {},
+                Err(err) if i < last && err.signal() == Some(SIGPIPE)   => {}, // every later stage succeeded
+                Err(err)                                                => return Err(self.error(i, err)),
+            }
+        }
+        Ok(())
+    }
+
+    fn error(&self, stage: usize, error: CommandError) -> PipelineError {
+        PipelineError { pipeline: Box::new(self.clone()), stage, error }
+    }
+}
+
+impl Display for Pipeline {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "`")?;
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 { write!(fmt, " | ")?; }
+            write!(fmt, "{}", stage.display_native())?;
+        }
+        write!(fmt, "`")
+    }
+}
+
+
+
+/// A stage of a [Pipeline] failed
+#[derive(Debug)]
+pub struct PipelineError {
+    pipeline:   Box<Pipeline>,
+    stage:      usize,
+    error:      CommandError,
+}
+
+impl PipelineError {
+    /// The [Pipeline] that failed
+    pub fn pipeline(&self) -> &Pipeline { &self.pipeline }
+
+    /// The index of the stage that failed (the last one, if several did)
+    pub fn stage(&self) -> usize { self.stage }
+
+    /// How the stage failed
+    pub fn error(&self) -> &CommandError { &self.error }
+}
+
+impl Display for PipelineError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "{} failed: {}", self.pipeline, self.error)
+    }
+}
+
+impl std::error::Error for PipelineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { Some(&self.error) }
+}
+
+impl From<PipelineError> for io::Error {
+    fn from(err: PipelineError) -> Self { io::Error::new(err.error.io_kind(), err) }
+}
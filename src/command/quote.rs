@@ -0,0 +1,191 @@
+//! Splitting and quoting of command lines for POSIX shells and Windows'
+//! [`CommandLineToArgvW`](https://docs.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-commandlinetoargvw)
+
+use std::fmt::{self, Formatter, Write};
+
+
+
+/// Split a POSIX `sh` style command line (`'single'`, `"double"`, and `\` escapes - no expansion.)
+pub(crate) fn split_posix(original: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut s = original.chars().peekable();
+
+    loop {
+        while s.peek().is_some_and(|ch| is_posix_space(*ch)) { let _space = s.next(); }
+        if s.peek().is_none() { break }
+
+        let mut arg = String::new();
+        while let Some(ch) = s.next() {
+            match ch {
+                ch if is_posix_space(ch) => break,
+                '\'' => loop {
+                    match s.next() {
+                        Some('\'')  => break,
+                        Some(ch)    => arg.push(ch),
+                        None        => return Err(format!("unable to parse `{}`: single quoted arg was never terminated", original)),
+                    }
+                },
+                '\"' => loop {
+                    match s.next() {
+                        Some('\"')  => break,
+                        Some('\\')  => match s.next() {
+                            Some('\n')                                      => {}, // line continuation
+                            Some(ch) if "$`\"\\".contains(ch)               => arg.push(ch),
+                            Some(ch)                                        => { arg.push('\\'); arg.push(ch); },
+                            None                                            => return Err(format!("unable to parse `{}`: double quoted arg was never terminated", original)),
+                        },
+                        Some(ch)    => arg.push(ch),
+                        None        => return Err(format!("unable to parse `{}`: double quoted arg was never terminated", original)),
+                    }
+                },
+                '\\' => match s.next() {
+                    Some('\n')  => {}, // line continuation
+                    Some(ch)    => arg.push(ch),
+                    None        => return Err(format!("unable to parse `{}`: trailing backslash", original)),
+                },
+                ch => arg.push(ch),
+            }
+        }
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+/// Split a Windows command line following the rules of `CommandLineToArgvW` / the MSVC CRT.
+pub(crate) fn split_windows(original: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut s = original.trim_matches(|ch: char| ch.is_ascii_whitespace()).chars().peekable();
+    if s.peek().is_none() { return args }
+
+    // The executable name is special: if it starts with a quote, it runs until the next quote (and the next arg starts
+    // immediately after), otherwise it runs until whitespace.  Either way, there are no escapes.
+    let mut exe = String::new();
+    if s.next_if_eq(&'\"').is_some() {
+        for ch in s.by_ref() {
+            if ch == '\"' { break }
+            exe.push(ch);
+        }
+    } else {
+        while let Some(ch) = s.next_if(|ch| !is_windows_space(*ch)) { exe.push(ch); }
+    }
+    args.push(exe);
+
+    loop {
+        while s.peek().is_some_and(|ch| is_windows_space(*ch)) { let _space = s.next(); }
+        if s.peek().is_none() { break }
+
+        let mut arg = String::new();
+        let mut quoted = false;
+        while let Some(ch) = s.next() {
+            match ch {
+                ' ' | '\t' if !quoted           => break,
+                '\\' => {
+                    let mut backslashes = 1;
+                    while s.peek() == Some(&'\\') { let _bs = s.next(); backslashes += 1; }
+                    if s.peek() == Some(&'\"') {
+                        for _ in 0 .. backslashes / 2 { arg.push('\\'); }
+                        if backslashes % 2 == 1 { let _quote = s.next(); arg.push('\"'); }
+                    } else {
+                        for _ in 0 .. backslashes { arg.push('\\'); }
+                    }
+                },
+                '\"' if quoted && s.peek() == Some(&'\"') => { let _quote = s.next(); arg.push('\"'); },
+                '\"'                            => quoted = !quoted,
+                ch                              => arg.push(ch),
+            }
+        }
+        args.push(arg);
+    }
+
+    args
+}
+
+
+
+/// Write `arg` such that [split_posix] will parse it back as a single, identical arg.
+pub(crate) fn write_posix(fmt: &mut Formatter, arg: &str) -> fmt::Result {
+    if !arg.is_empty() && arg.chars().all(|ch| ch.is_ascii_alphanumeric() || "_-./:=@%+,".contains(ch)) {
+        fmt.write_str(arg)
+    } else {
+        fmt.write_char('\'')?;
+        for (i, part) in arg.split('\'').enumerate() {
+            if i != 0 { fmt.write_str("'\\''")?; }
+            fmt.write_str(part)?;
+        }
+        fmt.write_char('\'')
+    }
+}
+
+/// Write `exe` such that [split_windows] will parse it back as the same executable name.
+///
+/// Windows executable names cannot contain `"`, so no escaping is attempted.
+pub(crate) fn write_windows_exe(fmt: &mut Formatter, exe: &str) -> fmt::Result {
+    if exe.is_empty() || exe.contains(is_windows_space) {
+        write!(fmt, "\"{}\"", exe)
+    } else {
+        fmt.write_str(exe)
+    }
+}
+
+/// Write `arg` such that [split_windows] will parse it back as a single, identical arg.
+pub(crate) fn write_windows(fmt: &mut Formatter, arg: &str) -> fmt::Result {
+    let quote = arg.is_empty() || arg.contains(|ch: char| ch.is_ascii_whitespace());
+    if quote { fmt.write_char('\"')?; }
+    let mut backslashes = 0;
+    for ch in arg.chars() {
+        if ch == '\\' {
+            backslashes += 1;
+        } else {
+            if ch == '\"' { for _ in 0 ..= backslashes { fmt.write_char('\\')?; } }
+            backslashes = 0;
+        }
+        fmt.write_char(ch)?;
+    }
+    if quote {
+        for _ in 0 .. backslashes { fmt.write_char('\\')?; }
+        fmt.write_char('\"')?;
+    }
+    Ok(())
+}
+
+
+
+fn is_posix_space(ch: char) -> bool { " \t\r\n".contains(ch) }
+fn is_windows_space(ch: char) -> bool { ch == ' ' || ch == '\t' }
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    const ARGS : &[&str] = &[
+        "", " ", "a", "a b", "a\tb", "a\nb", "'", "''", "\"", "\"\"", "\\", "\\\\", "a\\", "a\\\\", "\\\"", "a\\\"b",
+        "$HOME", "`cmd`", "!", "*", "a=b", "--flag=\"quoted value\"", "C:\\Program Files\\", "\\\\?\\C:\\", "ünïcödé",
+    ];
+
+    #[test] fn round_trip_posix() {
+        for arg in ARGS.iter().copied() {
+            struct D<'a>(&'a str);
+            impl std::fmt::Display for D<'_> { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "exe ")?; write_posix(fmt, self.0) } }
+            let line = D(arg).to_string();
+            assert_eq!(split_posix(&line).unwrap(), ["exe", arg], "{:?} rendered as {:?}", arg, line);
+        }
+    }
+
+    #[test] fn windows_exe() {
+        assert_eq!(split_windows(r#""C:\a b\x.exe"arg"#),         [r"C:\a b\x.exe", "arg"]);
+        assert_eq!(split_windows(r#""C:\a b\x.exe" "a b""#),      [r"C:\a b\x.exe", "a b"]);
+        assert_eq!(split_windows(r#"C:\a"b"\x.exe arg"#),         [r#"C:\a"b"\x.exe"#, "arg"]);
+        assert_eq!(split_windows(r#"x.exe"#),                      ["x.exe"]);
+    }
+
+    #[test] fn round_trip_windows() {
+        for arg in ARGS.iter().copied() {
+            struct D<'a>(&'a str);
+            impl std::fmt::Display for D<'_> { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write_windows_exe(fmt, "C:\\Program Files\\exe")?; fmt.write_char(' ')?; write_windows(fmt, self.0) } }
+            let line = D(arg).to_string();
+            assert_eq!(split_windows(&line), ["C:\\Program Files\\exe", arg], "{:?} rendered as {:?}", arg, line);
+        }
+    }
+}
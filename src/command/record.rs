@@ -0,0 +1,99 @@
+use super::{Command, CommandErrorKind, exec::{self, Pipe}};
+
+use crate::scoped::{self, Registry};
+
+use std::cell::RefCell;
+use std::process::Output;
+use std::sync::{Arc, Mutex};
+
+
+
+/// Records [Command]s instead of running them, for dry runs and tests.
+///
+/// While active, [Command::status], [Command::output], and the [CommandExt](crate::CommandExt) methods log the command
+/// with [status!](crate::status) and "succeed" without spawning anything.  [Command::spawn] is unaffected.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// # use mmrbi::command::Recorder;
+/// let recorder = Recorder::new();
+/// recorder.stdout("1.0.0\n");
+/// {
+///     let _recording = recorder.record_thread();
+///     Command::new("cargo").args(&["publish", "--dry-run"]).current_dir("crates/foo").status0().unwrap();
+///     assert_eq!(Command::new("foo").arg("--version").stdout0().unwrap(), "1.0.0\n");
+/// }
+/// let commands = recorder.commands();
+/// assert_eq!(commands.len(), 2);
+/// assert_eq!(format!("{:?}", commands[0]), "`cargo publish --dry-run`, in `crates/foo`");
+/// ```
+#[derive(Clone, Default)]
+pub struct Recorder(Arc<Mutex<State>>);
+
+#[derive(Default)]
+struct State {
+    exit_code:  i32,
+    stdout:     Vec<u8>,
+    commands:   Vec<Command>,
+}
+
+impl Recorder {
+    /// Create a new recorder that reports exit code 0 with no output for every command.
+    pub fn new() -> Self { Self::default() }
+
+    /// Set the exit code reported for every recorded command (defaults to `0`.)
+    pub fn exit_code(&self, code: i32) -> &Self {
+        self.0.lock().unwrap().exit_code = code;
+        self
+    }
+
+    /// Set the stdout reported for every recorded command (defaults to nothing.)
+    pub fn stdout(&self, stdout: impl AsRef<[u8]>) -> &Self {
+        self.0.lock().unwrap().stdout = stdout.as_ref().into();
+        self
+    }
+
+    /// Record commands run on the current thread until the returned guard is dropped.
+    ///
+    /// Takes priority over any [Recorder::record_process] recorders.
+    pub fn record_thread(&self) -> RecordGuard {
+        RecordGuard { _guard: REGISTRY.push_thread(self.clone()) }
+    }
+
+    /// Record commands run on any thread until the returned guard is dropped.
+    pub fn record_process(&self) -> RecordGuard {
+        RecordGuard { _guard: REGISTRY.push_process(self.clone()) }
+    }
+
+    /// All commands recorded so far, in the order they were run.
+    pub fn commands(&self) -> Vec<Command> {
+        self.0.lock().unwrap().commands.clone()
+    }
+
+    /// The innermost active recorder for the current thread, if any.
+    pub(crate) fn active() -> Option<Self> {
+        REGISTRY.active()
+    }
+}
+
+/// Stops recording when dropped.  See [Recorder::record_thread] and [Recorder::record_process].
+#[must_use = "recording stops when the guard is dropped"]
+pub struct RecordGuard { _guard: scoped::Guard<Recorder> }
+
+thread_local! { static THREAD : scoped::Stack<Recorder> = const { RefCell::new(Vec::new()) }; }
+static REGISTRY : Registry<Recorder> = Registry::new(&THREAD);
+
+
+
+/// If a [Recorder] is active, record `cmd` and return its canned result instead of running it.
+pub(crate) fn intercept(cmd: &Command, stdout: &mut Pipe) -> Option<Result<Output, CommandErrorKind>> {
+    let recorder = Recorder::active()?;
+    crate::status!("Would run", "{:?}", cmd);
+    let mut state = recorder.0.lock().unwrap();
+    state.commands.push(cmd.clone());
+    let (status, out) = (exec::exit_status(state.exit_code), state.stdout.clone());
+    drop(state);
+    Some(exec::feed(stdout, out).map(|stdout| Output { status, stdout, stderr: Vec::new() }).map_err(CommandErrorKind::Io))
+}
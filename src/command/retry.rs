@@ -0,0 +1,104 @@
+use super::{Command, CommandError};
+
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+
+
+/// How to retry a flaky [Command].  See [Command::retry].
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of times to run the command, including the first (defaults to `3`.)
+    pub attempts:   u32,
+
+    /// How long to wait before the first retry, doubling for each subsequent retry (defaults to 1 second.)
+    pub backoff:    Duration,
+
+    /// Whether or not a failure is worth retrying (defaults to always.)
+    pub retry_if:   Arc<dyn Fn(&CommandError) -> bool + Send + Sync>,
+}
+
+impl Debug for RetryPolicy {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("RetryPolicy").field("attempts", &self.attempts).field("backoff", &self.backoff).finish_non_exhaustive()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts:   3,
+            backoff:    Duration::from_secs(1),
+            retry_if:   Arc::new(|_err| true),
+        }
+    }
+}
+
+impl Command {
+    /// Retry the [CommandExt](crate::CommandExt) `*0` methods (`status0`, `output0`, `stdout0`, `io0`, ...) if they fail.
+    ///
+    /// Each retry is logged with [warning!](crate::warning).  If every attempt fails, the final [CommandError] lists
+    /// the earlier attempts' failures as well (see [CommandError::previous_attempts].)  Stderr that would otherwise be
+    /// inherited is piped through this process's stderr instead, so [RetryPolicy::retry_if] can see its last few lines.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # use mmrbi::command::RetryPolicy;
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// # if cfg!(unix) {
+    /// let policy = RetryPolicy {
+    ///     attempts:   2,
+    ///     backoff:    Duration::from_millis(1),
+    ///     retry_if:   Arc::new(|err| err.stderr().iter().any(|line| line.contains("file lock"))),
+    /// };
+    /// let mut cmd = Command::parse_posix("sh -c 'echo Blocking waiting for file lock >&2; exit 101'").unwrap();
+    /// cmd.retry(policy);
+    ///
+    /// let err = cmd.status0().unwrap_err();
+    /// assert_eq!(err.previous_attempts().len(), 1);
+    /// assert_eq!(err.stderr(), ["Blocking waiting for file lock"]);
+    ///
+    /// let err = cmd.output0().unwrap_err();
+    /// assert_eq!(err.code(), Some(101));
+    /// assert_eq!(err.previous_attempts().len(), 1);
+    /// assert_eq!(err.to_string(), "\
+    /// `sh -c 'echo Blocking waiting for file lock >&2; exit 101'` failed: exit code 101
+    ///     Blocking waiting for file lock
+    ///     attempt 1 of 2: `sh -c 'echo Blocking waiting for file lock >&2; exit 101'` failed: exit code 101
+    ///         Blocking waiting for file lock");
+    /// # }
+    /// ```
+    pub fn retry(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Invoke `attempt` until it succeeds, or until [Command::retry]'s policy says to give up.
+    pub(crate) fn retrying<T>(&self, mut attempt: impl FnMut() -> Result<T, CommandError>) -> Result<T, CommandError> {
+        let policy = match self.retry.as_ref() {
+            Some(policy)    => policy,
+            None            => return attempt(),
+        };
+
+        let mut failures = Vec::new();
+        let mut backoff = policy.backoff;
+        let mut n = 0;
+        loop {
+            n += 1;
+            let err = match attempt() {
+                Ok(value)   => return Ok(value),
+                Err(err)    => err,
+            };
+            if n >= policy.attempts || !(policy.retry_if)(&err) { return Err(err.with_previous_attempts(failures)) }
+            crate::warning!("{}\nretrying in {:?} (attempt {} of {} failed)", err, backoff, n, policy.attempts);
+            failures.push(err);
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+}
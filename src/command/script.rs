@@ -0,0 +1,173 @@
+use super::{Command, quote};
+
+use std::fmt::{self, Formatter, Write};
+
+
+
+/// A shell to write [Command::to_script] scripts for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Shell {
+    /// A `bash` script (`.sh`)
+    Bash,
+
+    /// A windows `cmd` batch file (`.cmd`)
+    Cmd,
+
+    /// A PowerShell script (`.ps1`)
+    PowerShell,
+}
+
+impl Shell {
+    /// The conventional file extension for scripts for this shell (e.g. `"sh"`)
+    pub fn extension(self) -> &'static str {
+        match self {
+            Shell::Bash         => "sh",
+            Shell::Cmd          => "cmd",
+            Shell::PowerShell   => "ps1",
+        }
+    }
+}
+
+impl Command {
+    /// Render as a standalone script that reproduces this command's directory, environment, program, and args.
+    ///
+    /// Stdio configuration, [Command::stdin_bytes], and [Command::timeout] are not reproduced.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # use mmrbi::command::Shell;
+    /// let mut cmd = Command::new("cargo");
+    /// cmd.args(&["build", "--features", "a b"]).current_dir("crates/foo").env("RUSTFLAGS", "-D warnings").env_remove("RUSTUP_TOOLCHAIN");
+    ///
+    /// assert_eq!(cmd.to_script(Shell::Bash), "\
+    /// #!/usr/bin/env bash
+    /// set -e
+    /// cd crates/foo
+    /// unset RUSTUP_TOOLCHAIN
+    /// export RUSTFLAGS='-D warnings'
+    /// cargo build --features 'a b'
+    /// ");
+    ///
+    /// assert_eq!(cmd.to_script(Shell::Cmd), "\
+    /// @echo off
+    /// setlocal
+    /// cd /d crates/foo || exit /b 1
+    /// set \"RUSTUP_TOOLCHAIN=\"
+    /// set \"RUSTFLAGS=-D warnings\"
+    /// cargo build --features \"a b\"
+    /// ");
+    ///
+    /// assert_eq!(cmd.to_script(Shell::PowerShell), "\
+    /// $ErrorActionPreference = 'Stop'
+    /// Set-Location -LiteralPath 'crates/foo'
+    /// [Environment]::SetEnvironmentVariable('RUSTUP_TOOLCHAIN', $null)
+    /// [Environment]::SetEnvironmentVariable('RUSTFLAGS', '-D warnings')
+    /// & 'cargo' 'build' '--features' 'a b'
+    /// exit $LASTEXITCODE
+    /// ");
+    ///
+    /// // bash can't `export` names that aren't identifiers, so they're passed through `env` instead
+    /// let mut cmd = Command::new("foo");
+    /// cmd.env("ProgramFiles(x86)", "C:\\Program Files (x86)");
+    /// assert!(cmd.to_script(Shell::Bash).ends_with("\nenv 'ProgramFiles(x86)=C:\\Program Files (x86)' foo\n"));
+    /// ```
+    pub fn to_script(&self, shell: Shell) -> String {
+        let script = Script { cmd: self, shell };
+        script.to_string()
+    }
+}
+
+struct Script<'c> {
+    cmd:    &'c Command,
+    shell:  Shell,
+}
+
+impl fmt::Display for Script<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let cmd     = self.cmd;
+        let dir     = cmd.dir.as_ref().map(|dir| dir.to_string_lossy());
+        let env     = cmd.env.iter().filter_map(|(k, v)| Some((k.to_string_lossy(), v.as_ref()?.to_string_lossy()))).collect::<Vec<_>>();
+        let removed = cmd.env.iter().filter(|(_, v)| v.is_none()).map(|(k, _)| k.to_string_lossy()).collect::<Vec<_>>();
+        let program = cmd.program.to_string_lossy();
+        let args    = cmd.args.iter().map(|a| a.to_string_lossy()).collect::<Vec<_>>();
+
+        match self.shell {
+            Shell::Bash => {
+                writeln!(fmt, "#!/usr/bin/env bash")?;
+                writeln!(fmt, "set -e")?;
+                if let Some(dir) = dir.as_ref() { write!(fmt, "cd ")?; quote::write_posix(fmt, dir)?; writeln!(fmt)?; }
+                if cmd.env_clear {
+                    write!(fmt, "env -i")?;
+                    for (k, v) in env.iter() { write!(fmt, " ")?; quote::write_posix(fmt, &format!("{}={}", k, v))?; }
+                    write!(fmt, " ")?;
+                } else {
+                    // keys that aren't shell identifiers (e.g. `ProgramFiles(x86)`) can't be `unset` or `export`ed, but `env` can handle them
+                    for k in removed.iter().filter(|k| is_identifier(k)) { writeln!(fmt, "unset {}", k)?; }
+                    for (k, v) in env.iter().filter(|(k, _)| is_identifier(k)) { write!(fmt, "export {}=", k)?; quote::write_posix(fmt, v)?; writeln!(fmt)?; }
+                    if removed.iter().chain(env.iter().map(|(k, _)| k)).any(|k| !is_identifier(k)) {
+                        write!(fmt, "env")?;
+                        for k in removed.iter().filter(|k| !is_identifier(k)) { write!(fmt, " -u ")?; quote::write_posix(fmt, k)?; }
+                        for (k, v) in env.iter().filter(|(k, _)| !is_identifier(k)) { write!(fmt, " ")?; quote::write_posix(fmt, &format!("{}={}", k, v))?; }
+                        write!(fmt, " ")?;
+                    }
+                }
+                quote::write_posix(fmt, &program)?;
+                for arg in args.iter() { write!(fmt, " ")?; quote::write_posix(fmt, arg)?; }
+                writeln!(fmt)
+            },
+            Shell::Cmd => {
+                writeln!(fmt, "@echo off")?;
+                writeln!(fmt, "setlocal")?;
+                if let Some(dir) = dir.as_ref() { write!(fmt, "cd /d ")?; write_cmd(fmt, dir, quote::write_windows_exe)?; writeln!(fmt, " || exit /b 1")?; }
+                if cmd.env_clear { writeln!(fmt, "for /f \"delims==\" %%v in ('set') do set \"%%v=\"")?; }
+                for k in removed.iter() { writeln!(fmt, "set \"{}=\"", k.replace('%', "%%"))?; }
+                for (k, v) in env.iter() { writeln!(fmt, "set \"{}={}\"", k.replace('%', "%%"), v.replace('%', "%%"))?; }
+                write_cmd(fmt, &program, quote::write_windows_exe)?;
+                for arg in args.iter() { write!(fmt, " ")?; write_cmd(fmt, arg, quote::write_windows)?; }
+                writeln!(fmt)
+            },
+            Shell::PowerShell => {
+                writeln!(fmt, "$ErrorActionPreference = 'Stop'")?;
+                if let Some(dir) = dir.as_ref() { writeln!(fmt, "Set-Location -LiteralPath {}", PsQuote(dir))?; }
+                if cmd.env_clear { writeln!(fmt, "Get-ChildItem env: | ForEach-Object {{ [Environment]::SetEnvironmentVariable($_.Name, $null) }}")?; }
+                for k in removed.iter() { writeln!(fmt, "[Environment]::SetEnvironmentVariable({}, $null)", PsQuote(k))?; }
+                for (k, v) in env.iter() { writeln!(fmt, "[Environment]::SetEnvironmentVariable({}, {})", PsQuote(k), PsQuote(v))?; }
+                write!(fmt, "& {}", PsQuote(&program))?;
+                for arg in args.iter() { write!(fmt, " {}", PsQuote(arg))?; }
+                writeln!(fmt)?;
+                writeln!(fmt, "exit $LASTEXITCODE")
+            },
+        }
+    }
+}
+
+/// Is `name` a valid POSIX shell variable name?
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|ch| ch == '_' || ch.is_ascii_alphabetic()) && chars.all(|ch| ch == '_' || ch.is_ascii_alphanumeric())
+}
+
+/// Quote `arg` with `write`, then escape `cmd` metacharacters (`%` everywhere, `^&|<>()` outside of quotes.)
+fn write_cmd(fmt: &mut Formatter, arg: &str, write: fn(&mut Formatter, &str) -> fmt::Result) -> fmt::Result {
+    let quoted = Quoted(arg, write).to_string();
+    let mut in_quotes = false;
+    for ch in quoted.chars() {
+        match ch {
+            '\"'                                        => in_quotes = !in_quotes,
+            '%'                                         => fmt.write_char('%')?,
+            '^' | '&' | '|' | '<' | '>' | '(' | ')' if !in_quotes => fmt.write_char('^')?,
+            _                                           => {},
+        }
+        fmt.write_char(ch)?;
+    }
+    Ok(())
+}
+
+struct Quoted<'a>(&'a str, fn(&mut Formatter, &str) -> fmt::Result);
+impl fmt::Display for Quoted<'_> { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { (self.1)(fmt, self.0) } }
+
+/// A PowerShell single quoted string literal
+struct PsQuote<'a>(&'a str);
+impl fmt::Display for PsQuote<'_> { fn fmt(&self, fmt: &mut Formatter) -> fmt::Result { write!(fmt, "'{}'", self.0.replace('\'', "''")) } }
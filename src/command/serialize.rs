@@ -0,0 +1,79 @@
+#![cfg(feature = "serde")]
+
+use super::Command;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::collections::BTreeMap;
+
+
+
+/// The serialized form of a [Command].  Non-UTF-8 strings are converted lossily.
+#[derive(Serialize, Deserialize)]
+struct SerdeCommand {
+    program:    String,
+    #[serde(default)] args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")] dir: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")] env_clear: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] env_remove: Vec<String>,
+    #[serde(default)] env: BTreeMap<String, String>,
+}
+
+/// Serializes the program, args, current directory, and environment of a [Command].
+/// Stdio configuration, [Command::stdin_bytes], and [Command::timeout] are not serialized.
+#[cfg_attr(doc_cfg, doc(cfg(feature = "serde")))]
+impl Serialize for Command {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerdeCommand {
+            program:    self.program.to_string_lossy().into(),
+            args:       self.args.iter().map(|a| a.to_string_lossy().into()).collect(),
+            dir:        self.dir.as_ref().map(|d| d.to_string_lossy().into()),
+            env_remove: self.env.iter().filter(|(_, v)| v.is_none()).map(|(k, _)| k.to_string_lossy().into()).collect(),
+            env:        self.env.iter().filter_map(|(k, v)| Some((k.to_string_lossy().into(), v.as_ref()?.to_string_lossy().into()))).collect(),
+            env_clear:  self.env_clear,
+        }.serialize(serializer)
+    }
+}
+
+#[cfg_attr(doc_cfg, doc(cfg(feature = "serde")))]
+impl<'de> Deserialize<'de> for Command {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let c = SerdeCommand::deserialize(deserializer)?;
+        let mut cmd = Command::new(c.program);
+        cmd.args(c.args);
+        if let Some(dir) = c.dir { cmd.current_dir(dir); }
+        if c.env_clear { cmd.env_clear(); }
+        for k in c.env_remove { cmd.env_remove(k); }
+        cmd.envs(c.env);
+        Ok(cmd)
+    }
+}
+
+
+
+#[cfg(all(test, feature = "toml"))] mod tests {
+    use super::*;
+
+    #[test] fn round_trip() {
+        let mut cmd = Command::new("cargo");
+        cmd.args(["build", "--features", "a b"]).current_dir("crates/foo").env_clear().env("RUSTFLAGS", "-D warnings");
+
+        let toml = toml::to_string(&cmd).unwrap();
+        assert_eq!(toml, "program = \"cargo\"\nargs = [\"build\", \"--features\", \"a b\"]\ndir = \"crates/foo\"\nenv_clear = true\n\n[env]\nRUSTFLAGS = \"-D warnings\"\n");
+
+        let cmd2 : Command = toml::from_str(&toml).unwrap();
+        assert_eq!(format!("{:?}", cmd2), format!("{:?}", cmd));
+
+        let mut cmd = Command::new("cargo");
+        cmd.env("RUSTFLAGS", "-D warnings").env_remove("RUSTUP_TOOLCHAIN");
+
+        let toml = toml::to_string(&cmd).unwrap();
+        assert_eq!(toml, "program = \"cargo\"\nargs = []\nenv_remove = [\"RUSTUP_TOOLCHAIN\"]\n\n[env]\nRUSTFLAGS = \"-D warnings\"\n");
+
+        let cmd2 : Command = toml::from_str(&toml).unwrap();
+        assert_eq!(format!("{:?}", cmd2), format!("{:?}", cmd));
+
+        let cmd3 : Command = toml::from_str("program = \"rustc\"").unwrap();
+        assert_eq!(format!("{:?}", cmd3), "`rustc`");
+    }
+}
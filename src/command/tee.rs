@@ -0,0 +1,88 @@
+//! Appending a [Command]'s output to a log file.  See [Command::log_to].
+
+use super::{Command, CommandError};
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitStatus;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+
+/// A log file that timestamped lines of output are appended to.
+///
+/// The file is closed by [Tee::footer]: any lines after that (e.g. from readers detached after a timeout) are discarded.
+pub(crate) struct Tee(Mutex<Option<File>>);
+
+impl Tee {
+    /// Open `path` for appending (creating parent directories as needed), and write a header describing `cmd`.
+    pub fn open(path: &Path, cmd: &Command) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) { fs::create_dir_all(dir)?; }
+        let tee = Self(Mutex::new(Some(OpenOptions::new().create(true).append(true).open(path)?)));
+        tee.write("running", format_args!("{:?}", cmd));
+        Ok(tee)
+    }
+
+    /// Append a line of output from `stream` (`"stdout"` or `"stderr"`.)
+    pub fn line(&self, stream: &str, line: &[u8]) {
+        self.write(stream, format_args!("{}", String::from_utf8_lossy(line)));
+    }
+
+    /// Append a footer describing how the command finished, and close the log.
+    pub fn footer(&self, result: Result<ExitStatus, &CommandError>) {
+        match result {
+            Ok(status)  => self.write("finished", format_args!("{}", status)),
+            Err(err)    => self.write("failed", format_args!("{}", err)),
+        }
+        *self.0.lock().unwrap() = None;
+    }
+
+    /// Failing to write to the log shouldn't fail the command itself, so errors are ignored.
+    fn write(&self, tag: &str, message: fmt::Arguments) {
+        if let Some(file) = self.0.lock().unwrap().as_mut() {
+            let _ = writeln!(file, "[{}] {}: {}", Utc(SystemTime::now()), tag, message);
+        }
+    }
+}
+
+
+
+/// Formats as an ISO 8601 UTC timestamp with millisecond precision, e.g. `2020-07-08T12:34:56.789Z`
+struct Utc(SystemTime);
+
+impl Display for Utc {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let since_epoch = self.0.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs();
+        let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+        // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z   = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe/1460 + doe/36524 - doe/146096) / 365;
+        let doy = doe - (365*yoe + yoe/4 - yoe/100);
+        let mp  = (5*doy + 2) / 153;
+        let d   = doy - (153*mp + 2)/5 + 1;
+        let m   = if mp < 10 { mp + 3 } else { mp - 9 };
+        let y   = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+
+        write!(fmt, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", y, m, d, secs / 3600, secs / 60 % 60, secs % 60, since_epoch.subsec_millis())
+    }
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test] fn utc() {
+        assert_eq!(Utc(UNIX_EPOCH).to_string(), "1970-01-01T00:00:00.000Z");
+        assert_eq!(Utc(UNIX_EPOCH + Duration::from_millis(1_594_211_696_789)).to_string(), "2020-07-08T12:34:56.789Z");
+        assert_eq!(Utc(UNIX_EPOCH + Duration::from_secs(951_782_400)).to_string(), "2000-02-29T00:00:00.000Z");
+    }
+}
@@ -1,16 +1,17 @@
-use std::io::{self, BufRead, BufReader};
+use crate::command::{self, exec::Pipe, CommandError, CommandErrorKind, IoLine, OutputLines, StderrTail};
+
+use std::io;
 use std::process::{Command, ExitStatus, Stdio, Output};
-use std::thread;
 
 
 
 /// Utility methods for [std::process::Command]
 pub trait CommandExt {
     /// [Command::status], but returns an error if the process didn't have a zero exit code
-    fn status0(&mut self) -> io::Result<()>;
+    fn status0(&mut self) -> Result<(), CommandError>;
 
     /// [Command::output], but returns an error if the process didn't have a zero exit code
-    fn output0(&mut self) -> io::Result<Output>;
+    fn output0(&mut self) -> Result<Output, CommandError>;
 
     /// [Command::output], but:
     ///
@@ -18,7 +19,7 @@ pub trait CommandExt {
     /// * Returns an error if stdout wasn't valid unicode
     /// * Returns *only* stdout
     /// * Stderr is inherited instead of redirected
-    fn stdout0(&mut self) -> io::Result<String>;
+    fn stdout0(&mut self) -> Result<String, CommandError>;
 
     /// [Command::output], but:
     ///
@@ -26,79 +27,156 @@ pub trait CommandExt {
     /// * Returns an error if stdout wasn't valid unicode
     /// * Returns *only* stdout
     /// * Stderr is nulled instead of redirected
-    fn stdout0_no_stderr(&mut self) -> io::Result<String>;
+    fn stdout0_no_stderr(&mut self) -> Result<String, CommandError>;
 
     /// [Command::status], but provides a callback for stdout/stderr
+    ///
+    /// Returns an [io::ErrorKind::InvalidData] error if any line wasn't valid unicode.
+    /// See [CommandExt::io_lossy] or [CommandExt::io_bytes] to tolerate such output.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # if cfg!(unix) {
+    /// let err = Command::parse_posix(r"printf '\377\n'").unwrap().io(|_| {}, |_| {}).unwrap_err();
+    /// assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    /// # }
+    /// ```
     fn io(&mut self, on_out: impl Fn(&str) + Send + Sync + 'static, on_err: impl Fn(&str) + Send + Sync + 'static) -> io::Result<ExitStatus>;
 
     /// [Command::status], but provides a callback for stdout/stderr and returns an error if the process didn't have a zero exit code
-    fn io0(&mut self, on_out: impl Fn(&str) + Send + Sync + 'static, on_err: impl Fn(&str) + Send + Sync + 'static) -> io::Result<()>;
+    fn io0(&mut self, on_out: impl Fn(&str) + Send + Sync + 'static, on_err: impl Fn(&str) + Send + Sync + 'static) -> Result<(), CommandError>;
+
+    /// [CommandExt::io], but invalid unicode is replaced with `U+FFFD` instead of returning an error
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # if cfg!(unix) {
+    /// Command::parse_posix(r"printf 'a\377b\n'").unwrap().io0_lossy(
+    ///     |out| assert_eq!(out, "a\u{FFFD}b"),
+    ///     |err| panic!("unexpected stderr: {}", err),
+    /// ).unwrap();
+    /// # }
+    /// ```
+    fn io_lossy(&mut self, on_out: impl Fn(&str) + Send + Sync + 'static, on_err: impl Fn(&str) + Send + Sync + 'static) -> io::Result<ExitStatus>;
+
+    /// [CommandExt::io0], but invalid unicode is replaced with `U+FFFD` instead of returning an error
+    fn io0_lossy(&mut self, on_out: impl Fn(&str) + Send + Sync + 'static, on_err: impl Fn(&str) + Send + Sync + 'static) -> Result<(), CommandError>;
+
+    /// [CommandExt::io], but lines are passed as raw bytes (excluding the trailing `\n` or `\r\n`) without any unicode validation
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # if cfg!(unix) {
+    /// Command::parse_posix(r"printf 'a\377b\r\n'").unwrap().io0_bytes(
+    ///     |out| assert_eq!(out, b"a\xFFb"),
+    ///     |err| panic!("unexpected stderr: {:?}", err),
+    /// ).unwrap();
+    /// # }
+    /// ```
+    fn io_bytes(&mut self, on_out: impl Fn(&[u8]) + Send + Sync + 'static, on_err: impl Fn(&[u8]) + Send + Sync + 'static) -> io::Result<ExitStatus>;
+
+    /// [CommandExt::io0], but lines are passed as raw bytes (excluding the trailing `\n` or `\r\n`) without any unicode validation
+    fn io0_bytes(&mut self, on_out: impl Fn(&[u8]) + Send + Sync + 'static, on_err: impl Fn(&[u8]) + Send + Sync + 'static) -> Result<(), CommandError>;
+
+    /// [Command::status], but provides a single callback for both stdout and stderr.
+    ///
+    /// Lines are delivered in the order they were received, on the calling thread.
+    /// Note that the process may buffer stdout and stderr independently, which can affect the order they're received in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # if cfg!(unix) {
+    /// let mut stderr = Vec::new();
+    /// Command::parse_posix("sh -c 'echo a; echo b >&2; echo c'").unwrap().io_lines(|line| {
+    ///     if line.is_stderr() { stderr.push(line.to_string()) }
+    /// }).unwrap();
+    /// assert_eq!(stderr, ["b"]);
+    /// # }
+    /// ```
+    fn io_lines(&mut self, on_line: impl FnMut(IoLine)) -> io::Result<ExitStatus>;
+
+    /// [Command::output], but stdout and stderr are interleaved by line in the order they were received.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use mmrbi::*;
+    /// # if cfg!(unix) {
+    /// let output = Command::parse_posix("sh -c 'echo a; sleep 0.1; echo b >&2; sleep 0.1; echo c'").unwrap().output_lines().unwrap();
+    /// assert_eq!(output.lines, ["a", "b", "c"]);
+    /// assert!(output.lines[1].is_stderr());
+    /// assert_eq!(output.stdout().collect::<Vec<_>>(), ["a", "c"]);
+    /// # }
+    /// ```
+    fn output_lines(&mut self) -> io::Result<OutputLines>;
 }
 
 impl CommandExt for Command {
-    fn status0(&mut self) -> io::Result<()> {
-        let status = self.status()?;
-        match status.code() {
-            Some(0) => Ok(()),
-            Some(n) => Err(io::Error::new(io::ErrorKind::Other, format!("exit code {}", n))),
-            None    => Err(io::Error::new(io::ErrorKind::Other, "terminated by signal")),
-        }
+    fn status0(&mut self) -> Result<(), CommandError> {
+        let status = self.status().map_err(|err| error(self, CommandErrorKind::Spawn(err)))?;
+        check(self, status, Vec::new)
     }
 
-    fn output0(&mut self) -> io::Result<Output> {
-        let output = self.output()?;
-        match output.status.code() {
-            Some(0) => Ok(output),
-            Some(n) => Err(io::Error::new(io::ErrorKind::Other, format!("exit code {}", n))),
-            None    => Err(io::Error::new(io::ErrorKind::Other, "terminated by signal")),
-        }
+    fn output0(&mut self) -> Result<Output, CommandError> {
+        let output = self.output().map_err(|err| error(self, CommandErrorKind::Spawn(err)))?;
+        if !output.status.success() { CommandError::check_output(&(&*self).into(), &output)?; }
+        Ok(output)
     }
 
-    fn stdout0(&mut self) -> io::Result<String> {
-        let output = self.stderr(Stdio::inherit()).output()?;
-        match output.status.code() {
-            Some(0) => {},
-            Some(n) => return Err(io::Error::new(io::ErrorKind::Other, format!("exit code {}", n))),
-            None    => return Err(io::Error::new(io::ErrorKind::Other, "terminated by signal")),
-        }
-        String::from_utf8(output.stdout).map_err(|_err| io::Error::new(io::ErrorKind::InvalidData, "stdout contained invalid unicode"))
+    fn stdout0(&mut self) -> Result<String, CommandError> {
+        let output = self.stderr(Stdio::inherit()).output0()?;
+        String::from_utf8(output.stdout).map_err(|_err| error(self, CommandErrorKind::InvalidUtf8))
     }
 
-    fn stdout0_no_stderr(&mut self) -> io::Result<String> {
-        let output = self.stderr(Stdio::null()).output()?;
-        match output.status.code() {
-            Some(0) => {},
-            Some(n) => return Err(io::Error::new(io::ErrorKind::Other, format!("exit code {}", n))),
-            None    => return Err(io::Error::new(io::ErrorKind::Other, "terminated by signal")),
-        }
-        String::from_utf8(output.stdout).map_err(|_err| io::Error::new(io::ErrorKind::InvalidData, "stdout contained invalid unicode"))
+    fn stdout0_no_stderr(&mut self) -> Result<String, CommandError> {
+        let output = self.stderr(Stdio::null()).output0()?;
+        String::from_utf8(output.stdout).map_err(|_err| error(self, CommandErrorKind::InvalidUtf8))
     }
 
-    fn io(&mut self, on_out: impl Fn(&str) + Send + Sync + 'static, on_err: impl Fn(&str) + Send + Sync + 'static) -> io::Result<ExitStatus> {
-        let mut child = self.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
-
-        let stdout = child.stdout.take().map(|stdout| thread::spawn(move ||{
-            for line in BufReader::new(stdout).lines() {
-                on_out(&line.unwrap());
-            }
-        }));
-        let stderr = child.stderr.take().map(|stderr| thread::spawn(move ||{
-            for line in BufReader::new(stderr).lines() {
-                on_err(&line.unwrap());
-            }
-        }));
-        let es = child.wait()?;
-        stdout.map(|t| t.join().unwrap());
-        stderr.map(|t| t.join().unwrap());
-        Ok(es)
+    fn io       (&mut self, on_out: impl Fn(&str ) + Send + Sync + 'static, on_err: impl Fn(&str ) + Send + Sync + 'static) -> io::Result<ExitStatus>     { Ok(io (self, Pipe::utf8 (on_out), Pipe::utf8 (on_err))?) }
+    fn io0      (&mut self, on_out: impl Fn(&str ) + Send + Sync + 'static, on_err: impl Fn(&str ) + Send + Sync + 'static) -> Result<(), CommandError>   {    io0(self, Pipe::utf8 (on_out), Pipe::utf8 (on_err))   }
+    fn io_lossy (&mut self, on_out: impl Fn(&str ) + Send + Sync + 'static, on_err: impl Fn(&str ) + Send + Sync + 'static) -> io::Result<ExitStatus>     { Ok(io (self, Pipe::lossy(on_out), Pipe::lossy(on_err))?) }
+    fn io0_lossy(&mut self, on_out: impl Fn(&str ) + Send + Sync + 'static, on_err: impl Fn(&str ) + Send + Sync + 'static) -> Result<(), CommandError>   {    io0(self, Pipe::lossy(on_out), Pipe::lossy(on_err))   }
+    fn io_bytes (&mut self, on_out: impl Fn(&[u8]) + Send + Sync + 'static, on_err: impl Fn(&[u8]) + Send + Sync + 'static) -> io::Result<ExitStatus>     { Ok(io (self, Pipe::bytes(on_out), Pipe::bytes(on_err))?) }
+    fn io0_bytes(&mut self, on_out: impl Fn(&[u8]) + Send + Sync + 'static, on_err: impl Fn(&[u8]) + Send + Sync + 'static) -> Result<(), CommandError>   {    io0(self, Pipe::bytes(on_out), Pipe::bytes(on_err))   }
+
+    fn io_lines(&mut self, mut on_line: impl FnMut(IoLine)) -> io::Result<ExitStatus> {
+        command::exec::lines(self, &Default::default(), &mut on_line).map_err(|kind| error(self, kind).into())
     }
 
-    fn io0(&mut self, on_out: impl Fn(&str) + Send + Sync + 'static, on_err: impl Fn(&str) + Send + Sync + 'static) -> io::Result<()> {
-        let status = self.io(on_out, on_err)?;
-        match status.code() {
-            Some(0) => Ok(()),
-            Some(n) => Err(io::Error::new(io::ErrorKind::Other, format!("exit code {}", n))),
-            None    => Err(io::Error::new(io::ErrorKind::Other, "terminated by signal")),
-        }
+    fn output_lines(&mut self) -> io::Result<OutputLines> {
+        let mut lines = Vec::new();
+        let status = self.io_lines(|line| lines.push(line.into()))?;
+        Ok(OutputLines { status, lines })
     }
 }
+
+fn io(cmd: &mut Command, on_out: Pipe, on_err: Pipe) -> Result<ExitStatus, CommandError> {
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let output = command::exec::run(cmd, &Default::default(), on_out, on_err).map_err(|kind| error(cmd, kind))?;
+    Ok(output.status)
+}
+
+fn io0(cmd: &mut Command, on_out: Pipe, on_err: Pipe) -> Result<(), CommandError> {
+    let tail = StderrTail::default();
+    let status = io(cmd, on_out, on_err.tail(&tail))?;
+    check(cmd, status, || tail.take())
+}
+
+/// [CommandError::check], but only converting `cmd` on failure
+fn check(cmd: &Command, status: ExitStatus, stderr: impl FnOnce() -> Vec<String>) -> Result<(), CommandError> {
+    if status.success() { return Ok(()) }
+    CommandError::check(&cmd.into(), status, stderr)
+}
+
+fn error(cmd: &Command, kind: CommandErrorKind) -> CommandError {
+    CommandError::new(cmd.into(), kind)
+}
@@ -80,7 +80,7 @@ impl Dir {
     /// # use mmrbi::fs::snapshot::*;
     /// # use std::ffi::*;
     /// let src = Dir::read("src", |e| e.is_dir() || e.path().extension() == Some(OsStr::new("rs"))).unwrap();
-    /// for (actual, expected) in src.dirs().into_iter().zip(["cargo", "fs"]) {
+    /// for (actual, expected) in src.dirs().into_iter().zip(["cargo", "command", "fs", "io"]) {
     ///     assert!(actual.name() == expected);
     /// }
     /// ```
@@ -2,12 +2,22 @@
 /// <code style="display: block; padding: 0.25em; margin: 0.5em 0;"><span style="color: red; font-weight: bold">error\[E1234\]</span><span style="color: grey; font-weight: bold">:</span> an error message
 /// <span style="color: darkcyan; font-weight: bold"> --&gt; </span>examples/macros.rs:2:3</code>
 ///
+/// Inside build scripts, this is written with [cargo::script::out::warning](crate::cargo::script::out::warning) instead.
+///
 /// # Example
 ///
 /// ```rust
 /// # use mmrbi::*;
 /// error!(at: "examples/macros.rs", line: 2, col: 3, code: "E1234", "an {} message", "error");
 /// error!("an {} message", "error"); // all params optional
+///
+/// // if `at` is readable, the source line is shown with the span underlined
+/// error!(at: "examples/macros.rs", line: 4, col: 5, len: 8, "unexpected warning");
+/// error!(at: "examples/macros.rs", line: 4, col: 5, end_col: 13, "unexpected warning");
+///
+/// // `note:` and `help:` sub-diagnostics follow a `;`, optionally with their own location
+/// error!(code: "E1234", "an {} message", "error"; note: "some context", help: format!("try {}", "this"));
+/// error!("an error"; note(at: "examples/macros.rs", line: 1, col: 1): "defined here");
 /// ```
 #[macro_export] macro_rules! error { ($($tt:tt)*) => { $crate::_logln!($crate::_log_impl::Severity::Error,    $($tt)*) }; }
 
@@ -15,6 +25,8 @@
 /// <code style="display: block; padding: 0.25em; margin: 0.5em 0;"><span style="color: olive; font-weight: bold">warning\[E1234\]</span><span style="color: grey; font-weight: bold">:</span> a warning message
 /// <span style="color: darkcyan; font-weight: bold"> --&gt; </span>examples/macros.rs:2:3</code>
 ///
+/// Inside build scripts, this is written with [cargo::script::out::warning](crate::cargo::script::out::warning) instead.
+///
 /// # Example
 ///
 /// ```rust
@@ -49,19 +61,17 @@
 /// status!("Finished", "{} [{}] target(s) in {}s", "dev", "debuginfo", "0.91");
 /// ```
 #[macro_export] macro_rules! status {
-    ( $verb:expr, $fmt:literal $($tt:tt)* ) => {{
-        use std::io::Write;
-        let stderr = std::io::stderr();
-        let mut stderr = stderr.lock();
-        let _ = write!  (&mut stderr, "\u{001B}[32;1m{: >12}\u{001B}[0m ", $verb);
-        let _ = writeln!(&mut stderr, $fmt $($tt)*);
-    }};
+    ( $verb:expr, $fmt:literal $($tt:tt)* ) => {
+        $crate::_log_impl::status(&$verb, format_args!($fmt $($tt)*))
+    };
 }
 
 /// Display an error in the same style as cargo or rustc, then `exit(1)`:
 /// <code style="display: block; padding: 0.25em; margin: 0.5em 0;"><span style="color: red; font-weight: bold">error\[E1234\]</span><span style="color: grey; font-weight: bold">:</span> an error message
 /// <span style="color: darkcyan; font-weight: bold"> --&gt; </span>examples/macros.rs:2:3</code>
 ///
+/// Inside build scripts, this is written with [cargo::script::out::warning](crate::cargo::script::out::warning) instead.
+///
 /// # Example
 ///
 /// ```rust,no_run
@@ -86,12 +96,27 @@
 /// header!("  a header  ");
 /// ```
 #[macro_export] macro_rules! header {
-    ( $fmt:literal $($tt:tt)* ) => {{
-        use std::io::Write;
-        let stderr = std::io::stderr();
-        let mut stderr = stderr.lock();
-        let _ = writeln!(&mut stderr, concat!("\u{001B}[30;102m", $fmt, "\u{001B}[0m") $($tt)*);
-    }};
+    ( $fmt:literal $($tt:tt)* ) => {
+        $crate::_log_impl::header(format_args!($fmt $($tt)*))
+    };
+}
+
+
+
+/// Start a timed [Section](crate::Section), which ends when the returned guard is dropped:
+/// <code style="display: block; padding: 0.25em; margin: 0.5em 0;"><span style="color: black; background: #14CE14">Building wasm</span>
+/// <span style="color: green; font-weight: bold">&nbsp;&nbsp;&nbsp;&nbsp;Finished</span> building wasm in 3.2s</code>
+///
+/// # Example
+///
+/// ```rust
+/// # use mmrbi::*;
+/// let _s = section!("Building {}", "wasm");
+/// ```
+#[macro_export] macro_rules! section {
+    ( $fmt:literal $($tt:tt)* ) => {
+        $crate::Section::new(format!($fmt $($tt)*))
+    };
 }
 
 
@@ -99,28 +124,49 @@
 #[doc(hidden)] #[macro_export] macro_rules! _logln {
     ( $sev:expr, $($tt:tt)* ) => {{
         #[allow(unused_mut)]
-        let mut ctx = $crate::_log_impl::Context {
-            severity:   $sev,
-            code:       "",
-            at:         None,
-            line:       0,
-            col:        0,
-        };
+        let mut ctx = $crate::_log_impl::Context::new($sev);
         $crate::_logln_inner!( ctx, $($tt)* );
     }};
 }
 
 #[doc(hidden)] #[macro_export] macro_rules! _logln_inner {
-    ( $ctx:expr, code:   $code:expr, $($tt:tt)* ) => { let code = format!("[{}]", $code); $ctx.code = code.as_str(); $crate::_logln_inner!($ctx, $($tt)*); };
+    ( $ctx:expr, code:   $code:expr, $($tt:tt)* ) => { let code = $code.to_string(); $ctx.code = code.as_str(); $crate::_logln_inner!($ctx, $($tt)*); };
     ( $ctx:expr, at:     $at:expr,   $($tt:tt)* ) => { let at = $at; $ctx.at = Some(at.as_ref()); $crate::_logln_inner!($ctx, $($tt)*); };
     ( $ctx:expr, path:   $at:expr,   $($tt:tt)* ) => { let at = $at; $ctx.at = Some(at.as_ref()); $crate::_logln_inner!($ctx, $($tt)*); };
     ( $ctx:expr, line:   $line:expr, $($tt:tt)* ) => { $ctx.line = $line; $crate::_logln_inner!($ctx, $($tt)*); };
     ( $ctx:expr, col:    $col:expr,  $($tt:tt)* ) => { $ctx.col = $col; $crate::_logln_inner!($ctx, $($tt)*); };
     ( $ctx:expr, column: $col:expr,  $($tt:tt)* ) => { $ctx.col = $col; $crate::_logln_inner!($ctx, $($tt)*); };
+    ( $ctx:expr, len:    $len:expr,  $($tt:tt)* ) => { $ctx.len = $len; $crate::_logln_inner!($ctx, $($tt)*); };
+    ( $ctx:expr, end_col:    $col:expr, $($tt:tt)* ) => { $ctx.end_col = $col; $crate::_logln_inner!($ctx, $($tt)*); };
+    ( $ctx:expr, end_column: $col:expr, $($tt:tt)* ) => { $ctx.end_col = $col; $crate::_logln_inner!($ctx, $($tt)*); };
 
     // Terminal rule
-    ( $ctx:expr, $fmt:literal $($tt:tt)* ) => {
-        use ::std::io::Write;
-        $crate::_log_impl::write($ctx, |stderr| writeln!(stderr, $fmt $($tt)*));
+    ( $ctx:expr, $fmt:literal $($tt:tt)* ) => { $crate::_logln_fmt!($ctx, ($fmt) $($tt)*); };
+}
+
+/// Split `fmt, args...; children...` at the `;`
+#[doc(hidden)] #[macro_export] macro_rules! _logln_fmt {
+    ( $ctx:expr, ($($fmt:tt)*) ; $($children:tt)* ) => {
+        $crate::_logln_children!($ctx, $($children)*);
+        $crate::_log_impl::write($ctx, format_args!($($fmt)*));
     };
+    ( $ctx:expr, ($($fmt:tt)*) $next:tt $($tt:tt)* ) => { $crate::_logln_fmt!($ctx, ($($fmt)* $next) $($tt)*); };
+    ( $ctx:expr, ($($fmt:tt)*) ) => { $crate::_log_impl::write($ctx, format_args!($($fmt)*)); };
+}
+
+#[doc(hidden)] #[macro_export] macro_rules! _logln_children {
+    ( $ctx:expr $(,)? ) => {};
+    ( $ctx:expr, note: $msg:expr $(, $($tt:tt)*)? ) => { $ctx.children.push($crate::_log_impl::Child::note($msg)); $crate::_logln_children!($ctx $(, $($tt)*)?); };
+    ( $ctx:expr, help: $msg:expr $(, $($tt:tt)*)? ) => { $ctx.children.push($crate::_log_impl::Child::help($msg)); $crate::_logln_children!($ctx $(, $($tt)*)?); };
+    ( $ctx:expr, note($($loc:tt)*): $msg:expr $(, $($tt:tt)*)? ) => { $ctx.children.push($crate::_logln_child!($crate::_log_impl::Child::note($msg), $($loc)*)); $crate::_logln_children!($ctx $(, $($tt)*)?); };
+    ( $ctx:expr, help($($loc:tt)*): $msg:expr $(, $($tt:tt)*)? ) => { $ctx.children.push($crate::_logln_child!($crate::_log_impl::Child::help($msg), $($loc)*)); $crate::_logln_children!($ctx $(, $($tt)*)?); };
+}
+
+#[doc(hidden)] #[macro_export] macro_rules! _logln_child {
+    ( $child:expr $(,)? ) => { $child };
+    ( $child:expr, at:     $at:expr   $(, $($tt:tt)*)? ) => { $crate::_logln_child!($child.at($at)     $(, $($tt)*)?) };
+    ( $child:expr, path:   $at:expr   $(, $($tt:tt)*)? ) => { $crate::_logln_child!($child.at($at)     $(, $($tt)*)?) };
+    ( $child:expr, line:   $line:expr $(, $($tt:tt)*)? ) => { $crate::_logln_child!($child.line($line) $(, $($tt)*)?) };
+    ( $child:expr, col:    $col:expr  $(, $($tt:tt)*)? ) => { $crate::_logln_child!($child.col($col)   $(, $($tt)*)?) };
+    ( $child:expr, column: $col:expr  $(, $($tt:tt)*)? ) => { $crate::_logln_child!($child.col($col)   $(, $($tt)*)?) };
 }
@@ -0,0 +1,221 @@
+//! Cargo style progress bars, e.g. `    Building [=====>    ] 12/40: foo`
+
+use crate::_log_impl;
+
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+
+
+/// A cargo style progress bar, e.g. `    Building [=====>    ] 12/40: foo`.
+///
+/// On a terminal, the bar is redrawn in place on the last line of stderr, and [error!](crate::error),
+/// [warning!](crate::warning), [status!](crate::status) etc. are printed above it.  Otherwise (or when the active
+/// [LogSink](crate::_log_impl::LogSink) doesn't write to stderr), progress is reported with periodic [status!](crate::status) lines instead.
+///
+/// The bar is cleared when dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// let examples = ["a", "b", "c"];
+/// let progress = Progress::new("Building", examples.len());
+/// for example in examples.iter() {
+///     // ...build example...
+///     progress.tick(example);
+/// }
+/// ```
+pub struct Progress {
+    verb:       String,
+    total:      usize,
+    terminal:   bool,
+    state:      Mutex<State>,
+}
+
+struct State {
+    current:    usize,
+    last:       Option<Instant>,
+}
+
+impl Progress {
+    /// Start reporting progress on `total` steps, labeled with `verb` (e.g. `"Building"`)
+    pub fn new(verb: impl Into<String>, total: usize) -> Self {
+        let terminal = io::stderr().is_terminal()
+            && _log_impl::active_sink().is_none_or(|sink| sink.is_stderr())
+            && !_log_impl::build_script()
+            && _log_impl::error_format() == _log_impl::ErrorFormat::Human
+            && std::env::var_os("TERM").map_or(cfg!(windows), |term| term != "dumb");
+        Self { verb: verb.into(), total, terminal, state: Mutex::new(State { current: 0, last: None }) }
+    }
+
+    /// Advance by one step, having just finished `message`
+    pub fn tick(&self, message: impl Display) {
+        let mut state = self.state.lock().unwrap();
+        state.current += 1;
+        self.update(&mut state, &message.to_string());
+    }
+
+    /// Set the number of steps completed, currently working on `message`
+    pub fn set(&self, current: usize, message: impl Display) {
+        let mut state = self.state.lock().unwrap();
+        state.current = current;
+        self.update(&mut state, &message.to_string());
+    }
+
+    fn update(&self, state: &mut State, message: &str) {
+        let now = Instant::now();
+        let period = if self.terminal { REDRAW_PERIOD } else { STATUS_PERIOD };
+        let due = state.last.is_none_or(|last| now.duration_since(last) >= period) || state.current >= self.total;
+        if !due { return }
+        state.last = Some(now);
+
+        if self.terminal {
+            let line = self.line(state.current, message, _log_impl::color(), columns());
+            let mut bar = BAR.lock().unwrap_or_else(|err| err.into_inner());
+            let stderr = io::stderr();
+            let mut stderr = stderr.lock();
+            let _ = write!(stderr, "\r\u{001B}[K{}", line);
+            let _ = stderr.flush();
+            *bar = Some(line);
+        } else {
+            crate::status!(self.verb, "{}/{}: {}", state.current, self.total, message);
+        }
+    }
+
+    /// Render the bar, truncated to fit within `columns` so it doesn't wrap
+    fn line(&self, current: usize, message: &str, color: bool, columns: usize) -> String {
+        let filled = (BAR_WIDTH * current.min(self.total)).checked_div(self.total).unwrap_or(BAR_WIDTH);
+        let bar = match filled {
+            0                       => " ".repeat(BAR_WIDTH),
+            n if n == BAR_WIDTH     => "=".repeat(BAR_WIDTH),
+            n                       => format!("{}>{}", "=".repeat(n-1), " ".repeat(BAR_WIDTH-n)),
+        };
+        let plain = format!("{: >12} [{}] {}/{}: {}", self.verb, bar, current, self.total, message);
+        let mut width = 0;
+        let plain = plain.chars().take_while(|ch| { width += display_width(*ch); width < columns }).collect::<String>();
+        match plain.find(" [") {
+            Some(verb) if color => format!("\u{001B}[36;1m{}\u{001B}[0m{}", &plain[..verb], &plain[verb..]),
+            _                   => plain,
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if !self.terminal { return }
+        let mut bar = BAR.lock().unwrap_or_else(|err| err.into_inner());
+        if bar.take().is_some() {
+            let _ = write!(io::stderr().lock(), "\r\u{001B}[K");
+        }
+    }
+}
+
+/// The width of the terminal stderr is attached to, falling back on `$COLUMNS`, and then on 80
+fn columns() -> usize {
+    terminal_columns().filter(|c| *c > 0)
+        .or_else(|| std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()))
+        .unwrap_or(80)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn terminal_columns() -> Option<usize> {
+    #[repr(C)] struct WinSize { row: u16, col: u16, xpixel: u16, ypixel: u16 }
+    #[cfg(any(target_os = "linux", target_os = "android"))] const TIOCGWINSZ : std::os::raw::c_ulong = 0x5413;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))] const TIOCGWINSZ : std::os::raw::c_ulong = 0x40087468;
+    extern "C" { fn ioctl(fd: std::os::raw::c_int, request: std::os::raw::c_ulong, ...) -> std::os::raw::c_int; }
+    let mut size = WinSize { row: 0, col: 0, xpixel: 0, ypixel: 0 };
+    // SAFETY:  TIOCGWINSZ only writes a `WinSize` through the pointer, which is valid for the duration of the call
+    let r = unsafe { ioctl(2, TIOCGWINSZ, &mut size as *mut WinSize) };
+    if r == 0 { Some(size.col.into()) } else { None }
+}
+
+#[cfg(windows)]
+fn terminal_columns() -> Option<usize> {
+    #[repr(C)] struct Coord { x: i16, y: i16 }
+    #[repr(C)] struct SmallRect { left: i16, top: i16, right: i16, bottom: i16 }
+    #[repr(C)] struct ConsoleScreenBufferInfo { size: Coord, cursor_position: Coord, attributes: u16, window: SmallRect, maximum_window_size: Coord }
+    #[link(name = "kernel32")] extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut std::ffi::c_void;
+        fn GetConsoleScreenBufferInfo(console: *mut std::ffi::c_void, info: *mut ConsoleScreenBufferInfo) -> i32;
+    }
+    const STD_ERROR_HANDLE : u32 = -12i32 as u32;
+    // SAFETY:  `info` is plain old data, valid for the duration of the call
+    let info = unsafe {
+        let mut info = std::mem::zeroed::<ConsoleScreenBufferInfo>();
+        if GetConsoleScreenBufferInfo(GetStdHandle(STD_ERROR_HANDLE), &mut info) == 0 { return None }
+        info
+    };
+    usize::try_from(i32::from(info.window.right) - i32::from(info.window.left) + 1).ok()
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+fn terminal_columns() -> Option<usize> { None }
+
+/// The number of terminal columns `ch` occupies: 0 for control and combining characters, 2 for wide (mostly CJK and emoji) characters
+fn display_width(ch: char) -> usize {
+    match ch as u32 {
+        0x00 ..= 0x1F | 0x7F ..= 0x9F                       => 0, // control characters
+        0x0300 ..= 0x036F | 0x200B ..= 0x200F | 0xFE00 ..= 0xFE0F | 0xFE20 ..= 0xFE2F => 0, // combining marks, zero width spaces, variation selectors
+        0x1100 ..= 0x115F | 0x2E80 ..= 0x303E | 0x3041 ..= 0x33FF | 0x3400 ..= 0x4DBF | 0x4E00 ..= 0x9FFF
+        | 0xA000 ..= 0xA4CF | 0xAC00 ..= 0xD7A3 | 0xF900 ..= 0xFAFF | 0xFE30 ..= 0xFE4F | 0xFF00 ..= 0xFF60
+        | 0xFFE0 ..= 0xFFE6 | 0x1F300 ..= 0x1F64F | 0x1F900 ..= 0x1F9FF | 0x20000 ..= 0x3FFFD => 2,
+        _                                                   => 1,
+    }
+}
+
+/// Run `f` (which writes to stderr) with any progress bar cleared, then redraw the bar below whatever `f` wrote.
+pub(crate) fn interleave<R>(f: impl FnOnce() -> R) -> R {
+    let bar = BAR.lock().unwrap_or_else(|err| err.into_inner());
+    if bar.is_some() { let _ = write!(io::stderr().lock(), "\r\u{001B}[K"); }
+    let r = f();
+    if let Some(line) = bar.as_ref() {
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "{}", line);
+        let _ = stderr.flush();
+    }
+    r
+}
+
+const BAR_WIDTH     : usize     = 25;
+const REDRAW_PERIOD : Duration  = Duration::from_millis(50);
+const STATUS_PERIOD : Duration  = Duration::from_secs(5);
+
+/// The currently drawn progress bar line, if any
+static BAR : Mutex<Option<String>> = Mutex::new(None);
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn line() {
+        let progress = Progress { verb: "Building".into(), total: 40, terminal: false, state: Mutex::new(State { current: 0, last: None }) };
+        assert_eq!(progress.line( 0, "foo", false, 80), "    Building [                         ] 0/40: foo");
+        assert_eq!(progress.line(12, "foo", false, 80), "    Building [======>                  ] 12/40: foo");
+        assert_eq!(progress.line(40, "foo", false, 80), "    Building [=========================] 40/40: foo");
+        assert_eq!(progress.line(12, "foo", false, 40), "    Building [======>                  ");
+        assert_eq!(progress.line(12, "foo", true,  80), "\u{001B}[36;1m    Building\u{001B}[0m [======>                  ] 12/40: foo");
+        assert_eq!(progress.line(12, "日本語", false, 51), "    Building [======>                  ] 12/40: 日");
+    }
+
+    #[test] fn status_fallback() {
+        let capture = _log_impl::CaptureSink::new();
+        let _capturing = _log_impl::sink_thread(capture.clone());
+        let progress = Progress::new("Building", 3);
+        assert!(!progress.terminal);
+        for example in ["a", "b", "c"].iter() { progress.tick(example); }
+        drop(progress);
+        assert_eq!(capture.lines(), ["    Building 1/3: a", "    Building 3/3: c"]);
+    }
+
+    #[test] fn sinks() {
+        use _log_impl::{CaptureSink, LogSink, StderrSink};
+        assert!( StderrSink.is_stderr());
+        assert!(!CaptureSink::new().is_stderr());
+        assert!( (CaptureSink::new(), StderrSink).is_stderr()); // e.g. teeing to a FileSink keeps the bar
+        assert!(!(CaptureSink::new(), CaptureSink::new()).is_stderr());
+    }
+}
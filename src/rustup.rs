@@ -135,7 +135,7 @@ impl<'r> RustupToolchains<'r> {
 
     /// Uninstall a toolchain via `rustup toolchain install ${toolchain}`
     pub fn uninstall(&self, toolchain: impl AsRef<str>) -> io::Result<()> {
-        self.rustup(&["toolchain", "install", toolchain.as_ref()]).status0()
+        Ok(self.rustup(&["toolchain", "install", toolchain.as_ref()]).status0()?)
     }
 
     fn rustup<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(&self, args: I) -> Command {
@@ -219,12 +219,12 @@ impl<'t> ToolchainTargets<'t> {
     /// `rustup target add {target} --toolchain {toolchain}` - adds a target
     pub fn add(&self, target: impl AsRef<str>) -> io::Result<()> {
         if self.get(target.as_ref()).is_some() { return Ok(()) }
-        self.rustup(&["target", "add", target.as_ref()]).status0()
+        Ok(self.rustup(&["target", "add", target.as_ref()]).status0()?)
     }
 
     /// `rustup target remove {target} --toolchain {toolchain}` - removes a target
     pub fn remove(&self, target: impl AsRef<str>) -> io::Result<()> {
-        self.rustup(&["target", "remove", target.as_ref()]).status0()
+        Ok(self.rustup(&["target", "remove", target.as_ref()]).status0()?)
     }
 
     fn rustup<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(&self, args: I) -> Command {
@@ -255,3 +255,60 @@ impl Target {
     /// The entire target name as a string
     pub fn as_str(&self) -> &str { &self.0 }
 }
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::command::Mocks;
+
+    fn mocks() -> Mocks {
+        let mocks = Mocks::new();
+        mocks.on("rustup", ["--version"]).stdout("rustup 1.22.1 (b01adbbc3 2020-07-08)\n");
+        mocks.on("rustup", ["show", "active-toolchain"]).stdout("stable-x86_64-unknown-linux-gnu (default)\n");
+        mocks.on("rustup", ["default"]).stdout("stable-x86_64-unknown-linux-gnu (default)\n");
+        mocks.on("rustup", ["toolchain", "list"]).stdout("stable-x86_64-unknown-linux-gnu (default)\nnightly-x86_64-unknown-linux-gnu\n");
+        mocks.on("rustup", ["*", "show", "active-toolchain"]).exit_code(1).stderr("error: toolchain 'nonexistant' is not installed\n");
+        mocks.on("rustup", ["+nightly", "show", "active-toolchain"]).stdout("nightly-x86_64-unknown-linux-gnu (overridden by +toolchain on the command line)\n");
+        mocks.on("rustup", ["target", "list", "--toolchain", "*"]).stdout("wasm32-unknown-unknown\nx86_64-unknown-linux-gnu (installed)\n");
+        mocks.on("rustup", ["target", "list", "--installed", "--toolchain", "*"]).stdout("x86_64-unknown-linux-gnu\n");
+        mocks.on("rustup", ["target", "add", "**"]);
+        mocks
+    }
+
+    #[test] fn toolchains() {
+        let mocks = mocks();
+        let _mocking = mocks.mock_thread();
+
+        let rustup = Rustup::default().unwrap();
+        assert!(rustup.is_available());
+        #[cfg(feature = "version")] assert_eq!(rustup.version().version, semver::Version::new(1, 22, 1));
+
+        let toolchains = rustup.toolchains();
+        assert_eq!(toolchains.active().unwrap().as_str(), "stable-x86_64-unknown-linux-gnu");
+        assert_eq!(toolchains.default().unwrap().as_str(), "stable-x86_64-unknown-linux-gnu");
+        assert_eq!(toolchains.installed().iter().map(|t| t.as_str()).collect::<Vec<_>>(), ["nightly-x86_64-unknown-linux-gnu", "stable-x86_64-unknown-linux-gnu"]);
+        assert_eq!(toolchains.get("nightly").unwrap().as_str(), "nightly-x86_64-unknown-linux-gnu");
+        assert!(toolchains.get("nonexistant").is_none());
+
+        let get = mocks.calls().into_iter().rfind(|c| c.display_posix().to_string() == "rustup +nightly show active-toolchain").unwrap();
+        assert_eq!(get.to_command().get_envs().collect::<Vec<_>>(), [("RUSTUP_TOOLCHAIN".as_ref(), None)]);
+    }
+
+    #[test] fn targets() {
+        let mocks = mocks();
+        let _mocking = mocks.mock_thread();
+
+        let toolchain = Rustup::new_unchecked("rustup").toolchains().active().unwrap();
+        let targets = toolchain.targets();
+        assert_eq!(targets.all().len(), 2);
+        assert_eq!(targets.installed().len(), 1);
+        assert!(targets.get("x86_64-unknown-linux-gnu").is_some());
+        assert!(targets.get("wasm32-unknown-unknown").is_none());
+
+        targets.add("x86_64-unknown-linux-gnu").unwrap();
+        targets.add("wasm32-unknown-unknown").unwrap();
+        let adds = mocks.calls().into_iter().map(|c| c.display_posix().to_string()).filter(|c| c.contains(" add ")).collect::<Vec<_>>();
+        assert_eq!(adds, ["rustup target add wasm32-unknown-unknown --toolchain stable-x86_64-unknown-linux-gnu"]);
+    }
+}
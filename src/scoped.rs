@@ -0,0 +1,109 @@
+//! Stacks of values that are active for the current thread, or the whole process, until a guard is dropped
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::LocalKey;
+
+
+
+/// The per-thread stack of a [Registry], for use with `thread_local!`
+pub(crate) type Stack<T> = RefCell<Vec<(u64, T)>>;
+
+/// A per-thread stack and a process-wide stack of `T`s, where the innermost per-thread value takes priority.
+///
+/// Used for [Mocks](crate::command::Mocks), [Recorder](crate::command::Recorder), and [LogSink](crate::_log_impl::LogSink)s.
+pub(crate) struct Registry<T: 'static> {
+    thread:     &'static LocalKey<Stack<T>>,
+    process:    Mutex<Vec<(u64, T)>>,
+}
+
+impl<T: Clone + 'static> Registry<T> {
+    /// Create a registry using `thread` (which should be exclusive to this registry) as the per-thread stack.
+    pub const fn new(thread: &'static LocalKey<Stack<T>>) -> Self {
+        Self { thread, process: Mutex::new(Vec::new()) }
+    }
+
+    /// Make `value` active on the current thread until the returned guard is dropped.
+    pub fn push_thread(&'static self, value: T) -> Guard<T> {
+        let id = next_id();
+        self.thread.with(|t| t.borrow_mut().push((id, value)));
+        Guard { registry: self, id, process: false, _not_send: PhantomData }
+    }
+
+    /// Make `value` active on every thread until the returned guard is dropped.
+    pub fn push_process(&'static self, value: T) -> Guard<T> {
+        let id = next_id();
+        self.process.lock().unwrap_or_else(|err| err.into_inner()).push((id, value));
+        Guard { registry: self, id, process: true, _not_send: PhantomData }
+    }
+
+    /// The innermost value for the current thread, if any.
+    ///
+    /// Falls back on the process-wide stack if thread locals have already been destroyed (e.g. during thread exit.)
+    pub fn active(&self) -> Option<T> {
+        let thread = self.thread.try_with(|t| t.borrow().last().map(|(_, v)| v.clone())).ok().flatten();
+        thread.or_else(|| self.process.lock().unwrap_or_else(|err| err.into_inner()).last().map(|(_, v)| v.clone()))
+    }
+}
+
+/// Removes a value from its [Registry] when dropped.
+///
+/// Not [Send], since a [Registry::push_thread] guard must be dropped on the thread whose stack it was pushed onto.
+pub(crate) struct Guard<T: 'static> {
+    registry:   &'static Registry<T>,
+    id:         u64,
+    process:    bool,
+    _not_send:  PhantomData<*const ()>,
+}
+
+impl<T: 'static> Drop for Guard<T> {
+    fn drop(&mut self) {
+        let remove = |stack: &mut Vec<(u64, T)>| {
+            if let Some(i) = stack.iter().rposition(|(id, _)| *id == self.id) { stack.remove(i); }
+        };
+        if self.process {
+            remove(&mut self.registry.process.lock().unwrap_or_else(|err| err.into_inner()));
+        } else {
+            let _ = self.registry.thread.try_with(|t| remove(&mut t.borrow_mut()));
+        }
+    }
+}
+
+fn next_id() -> u64 {
+    static NEXT : AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    thread_local! { static THREAD : Stack<u32> = const { RefCell::new(Vec::new()) }; }
+    static REGISTRY : Registry<u32> = Registry::new(&THREAD);
+
+    #[test] fn nesting() {
+        assert_eq!(REGISTRY.active(), None);
+        let a = REGISTRY.push_thread(1);
+        let b = REGISTRY.push_thread(2);
+        let c = REGISTRY.push_thread(1);
+        assert_eq!(REGISTRY.active(), Some(1));
+        drop(b); // out of order
+        assert_eq!(REGISTRY.active(), Some(1));
+        drop(c);
+        assert_eq!(REGISTRY.active(), Some(1));
+        drop(a);
+        assert_eq!(REGISTRY.active(), None);
+
+        let p = REGISTRY.push_process(3);
+        assert_eq!(REGISTRY.active(), Some(3));
+        let t = REGISTRY.push_thread(4);
+        assert_eq!(REGISTRY.active(), Some(4)); // thread takes priority
+        drop(p);
+        assert_eq!(REGISTRY.active(), Some(4));
+        drop(t);
+        assert_eq!(REGISTRY.active(), None);
+    }
+}
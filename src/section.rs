@@ -0,0 +1,181 @@
+//! Timed sections of a build, with a timing summary at process exit
+
+use crate::_log_impl;
+
+use std::cell::Cell;
+use std::sync::{Mutex, Once};
+use std::time::{Duration, Instant};
+
+
+
+/// A timed section of a build, usually created with [section!](crate::section).
+///
+/// Prints a [header!](crate::header) when created, and `Finished {name} in {time}` (or `Failed ...` if an
+/// [error!](crate::error) was logged on the same thread or a panic occurred in the meantime) when dropped.  Sections
+/// nest (per thread), and a tree of every section's timing is printed when the process exits.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::*;
+/// let _s = Section::new("Building wasm");
+/// {
+///     let _s = section!("Running {}", "wasm-bindgen");
+/// }
+/// // stderr:
+/// // Building wasm
+/// // Running wasm-bindgen
+/// //     Finished running wasm-bindgen in 0.0s
+/// //     Finished building wasm in 0.0s
+/// // Timings
+/// //         0.0s Building wasm
+/// //         0.0s   Running wasm-bindgen
+/// ```
+#[must_use = "the section ends when dropped"]
+pub struct Section {
+    index:  usize,
+    parent: Option<usize>,
+    start:  Instant,
+    errors: usize,
+}
+
+#[derive(Clone)]
+struct Node {
+    name:       String,
+    parent:     Option<usize>,
+    start:      Instant,
+    elapsed:    Option<Duration>,
+    failed:     bool,
+}
+
+impl Section {
+    /// Start a section named `name` (e.g. `"Building wasm"`)
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        crate::header!("{}", name);
+
+        ATEXIT.call_once(|| {
+            // SAFETY:  `atexit` is part of the C standard library, which std already links against.
+            // `print_timings` doesn't unwind, and only touches statics that remain valid during exit.
+            extern "C" { fn atexit(f: extern "C" fn()) -> std::os::raw::c_int; }
+            unsafe { atexit(print_timings); }
+        });
+
+        let start = Instant::now();
+        let parent = CURRENT.with(Cell::get);
+        let mut sections = SECTIONS.lock().unwrap_or_else(|err| err.into_inner());
+        sections.push(Node { name, parent, start, elapsed: None, failed: false });
+        let index = sections.len() - 1;
+        drop(sections);
+        CURRENT.with(|c| c.set(Some(index)));
+        Self { index, parent, start, errors: _log_impl::thread_errors() }
+    }
+}
+
+impl Drop for Section {
+    fn drop(&mut self) {
+        let _ = CURRENT.try_with(|c| c.set(self.parent));
+        let elapsed = self.start.elapsed();
+        let failed  = std::thread::panicking() || _log_impl::thread_errors() > self.errors;
+
+        let mut sections = SECTIONS.lock().unwrap_or_else(|err| err.into_inner());
+        let node = &mut sections[self.index];
+        node.elapsed = Some(elapsed);
+        node.failed  = failed;
+        let name = lowercase_first(&node.name);
+        drop(sections);
+
+        crate::status!(if failed { "Failed" } else { "Finished" }, "{} in {:.1}s", name, elapsed.as_secs_f64());
+    }
+}
+
+/// `"Building wasm"` → `"building wasm"`, but leave acronyms such as `"WASM"` alone
+fn lowercase_first(name: &str) -> String {
+    let first_word = name.split_whitespace().next().unwrap_or("");
+    if first_word.chars().filter(|ch| ch.is_uppercase()).count() > 1 { return name.into() }
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| first.to_lowercase().chain(chars).collect())
+}
+
+extern "C" fn print_timings() {
+    let _ = std::panic::catch_unwind(|| {
+        let sections = SECTIONS.lock().unwrap_or_else(|err| err.into_inner()).clone(); // don't hold the lock while logging
+        if sections.is_empty() { return }
+        crate::header!("Timings");
+        print_tree(&sections, None, 0);
+    });
+}
+
+/// Print the timings of the children of `parent` (the roots if `None`), and their children, indented by `depth`
+fn print_tree(nodes: &[Node], parent: Option<usize>, depth: usize) {
+    for (index, node) in nodes.iter().enumerate().filter(|(_, node)| node.parent == parent) {
+        let elapsed = node.elapsed.unwrap_or_else(|| node.start.elapsed());
+        let note = match (node.elapsed, node.failed) {
+            (None, _)       => " (unfinished)",
+            (_, true)       => " (failed)",
+            (_, false)      => "",
+        };
+        crate::status!(format!("{:.1}s", elapsed.as_secs_f64()), "{:indent$}{}{}", "", node.name, note, indent = 2 * depth);
+        print_tree(nodes, Some(index), depth + 1);
+    }
+}
+
+static ATEXIT   : Once = Once::new();
+static SECTIONS : Mutex<Vec<Node>> = Mutex::new(Vec::new());
+thread_local! { static CURRENT : Cell<Option<usize>> = const { Cell::new(None) }; }
+
+
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::_log_impl::{self, CaptureSink};
+
+    #[test] fn finished_failed() {
+        let capture = CaptureSink::new();
+        let _capturing = _log_impl::sink_thread(capture.clone());
+        {
+            let _a = Section::new("Building a");
+            let _b = crate::section!("Running {}", "b");
+        }
+        {
+            let _c = Section::new("Building c");
+            crate::error!("oops");
+        }
+        assert_eq!(capture.lines(), [
+            "Building a",
+            "Running b",
+            "    Finished running b in 0.0s",
+            "    Finished building a in 0.0s",
+            "Building c",
+            "      Failed building c in 0.0s",
+        ]);
+        SECTIONS.lock().unwrap().clear(); // don't print these timings when the test binary exits
+    }
+
+    #[test] fn tree() {
+        let capture = CaptureSink::new();
+        let _capturing = _log_impl::sink_thread(capture.clone());
+        let start = Instant::now();
+        let node = |name: &str, parent, elapsed, failed| Node { name: name.into(), parent, start, elapsed, failed };
+        print_tree(&[
+            node("Building a",  None,       Some(Duration::from_millis(1500)),  false),
+            node("Building b",  None,       Some(Duration::from_millis(200)),   true),
+            node("Running a",   Some(0),    Some(Duration::from_millis(1000)),  false),
+            node("Running b",   Some(1),    Some(Duration::from_millis(100)),   true),
+            node("Nested",      Some(2),    None,                               false),
+        ], None, 0);
+        assert_eq!(capture.lines(), [
+            "        1.5s Building a",
+            "        1.0s   Running a",
+            "        0.0s     Nested (unfinished)",
+            "        0.2s Building b (failed)",
+            "        0.1s   Running b (failed)",
+        ]);
+    }
+
+    #[test] fn lowercase_first() {
+        assert_eq!(super::lowercase_first("Building wasm"), "building wasm");
+        assert_eq!(super::lowercase_first("WASM build"), "WASM build");
+        assert_eq!(super::lowercase_first(""), "");
+    }
+}
@@ -12,8 +12,12 @@ use std::io;
 ///
 /// ```rust
 /// # use mmrbi::wasm_pack;
+/// # let mocks = mmrbi::command::Mocks::new();
+/// # mocks.on("wasm-pack", ["--version"]).stdout("wasm-pack 0.9.1\n");
+/// # let _mocking = mocks.mock_thread();
 /// let v = wasm_pack::version().unwrap();
 /// assert_eq!(v.tool_name, "wasm-pack");
+/// assert_eq!(v.version.to_string(), "0.9.1");
 /// ```
 #[cfg(feature = "version")]
 #[cfg_attr(doc_cfg, doc(cfg(feature = "version")))]
@@ -36,5 +40,27 @@ pub fn install_at_least(requested: &str) -> io::Result<()> {
     if let Ok(installed) = version() {
         if requested <= installed.version { return Ok(()); }
     }
-    Command::new("cargo").arg("install").arg("--version").arg(format!("^{}", requested)).arg("wasm-pack").status0()
+    Ok(Command::new("cargo").arg("install").arg("--version").arg(format!("^{}", requested)).arg("wasm-pack").status0()?)
+}
+
+
+
+#[cfg(all(test, feature = "version"))] mod tests {
+    use super::*;
+    use crate::command::Mocks;
+
+    #[test] fn install_at_least() {
+        let mocks = Mocks::new();
+        mocks.on("wasm-pack", ["--version"]).stdout("wasm-pack 0.9.1\n");
+        mocks.on("cargo", ["install", "**"]);
+        let _mocking = mocks.mock_thread();
+
+        assert_eq!(version().unwrap().version, semver::Version::new(0, 9, 1));
+        super::install_at_least("0.9.0").unwrap();
+        super::install_at_least("0.9.1").unwrap();
+        super::install_at_least("0.10.0").unwrap();
+
+        let installs = mocks.calls().into_iter().map(|c| c.display_windows().to_string()).filter(|c| c.starts_with("cargo ")).collect::<Vec<_>>();
+        assert_eq!(installs, ["cargo install --version ^0.10.0 wasm-pack"]);
+    }
 }
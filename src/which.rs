@@ -0,0 +1,109 @@
+//! Search `%PATH%` / `${PATH}` for executables
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+
+
+/// Find `name` in `%PATH%` / `${PATH}`, the same way spawning a [Command](crate::Command) would.
+///
+/// On windows, extensions from `%PATHEXT%` (e.g. `.exe`, `.cmd`) are tried as well.
+/// If `name` contains a path separator, `PATH` isn't searched, but `name` must still exist.
+///
+/// # Examples
+///
+/// ```rust
+/// # use mmrbi::which;
+/// let cargo = which("cargo").unwrap();
+/// assert!(cargo.is_absolute() || cargo.components().count() > 1);
+///
+/// assert!(which("nonexistent-mmrbi-command").is_none());
+/// ```
+pub fn which(name: impl AsRef<OsStr>) -> Option<PathBuf> {
+    search(name.as_ref(), env::var_os("PATH").as_deref(), None)
+}
+
+/// Find `name` in the directories of `path`, or relative to `cwd` if `name` contains a path separator.
+pub(crate) fn search(name: &OsStr, path: Option<&OsStr>, cwd: Option<&Path>) -> Option<PathBuf> {
+    if has_dir(name) {
+        let name = cwd.map_or_else(|| PathBuf::from(name), |cwd| cwd.join(name));
+        return candidates(&name).find(|c| is_executable(c));
+    }
+    dirs(name, path).into_iter().find_map(|dir| candidates(&dir.join(name)).find(|c| is_executable(c)))
+}
+
+/// The directories of `path` that would be searched for `name` (none if `name` contains a path separator.)
+pub(crate) fn dirs(name: &OsStr, path: Option<&OsStr>) -> Vec<PathBuf> {
+    if has_dir(name) { return Vec::new() }
+    // N.B. empty entries would mean the current directory on unix - which is a security footgun we don't emulate
+    path.map_or_else(Vec::new, |path| env::split_paths(path).filter(|dir| !dir.as_os_str().is_empty()).collect())
+}
+
+fn has_dir(name: &OsStr) -> bool {
+    let name = Path::new(name);
+    name.components().count() > 1 || name.is_absolute()
+}
+
+/// How to install `program`, if it's a well known tool.
+///
+/// If `program` is `cargo` running a well known subcommand, the hint covers installing that subcommand as well.
+pub(crate) fn install_hint(program: &OsStr, args: &[OsString]) -> Option<&'static str> {
+    if let Some((_, hint, _)) = cargo_subcommand(program, args) { return Some(hint) }
+    let stem = Path::new(program).file_stem()?.to_str()?;
+    HINTS.iter().find(|(tool, _)| tool.eq_ignore_ascii_case(stem)).map(|(_, hint)| *hint)
+}
+
+/// The well known cargo subcommand (and how to install it) that `program` `args` would run, if any.
+pub(crate) fn subcommand_install_hint(program: &OsStr, args: &[OsString]) -> Option<(&'static str, &'static str)> {
+    cargo_subcommand(program, args).map(|(subcommand, _, hint)| (*subcommand, *hint))
+}
+
+fn cargo_subcommand(program: &OsStr, args: &[OsString]) -> Option<&'static (&'static str, &'static str, &'static str)> {
+    if !Path::new(program).file_stem()?.to_str()?.eq_ignore_ascii_case("cargo") { return None }
+    let mut args = args.iter().map(|arg| arg.to_str()).peekable();
+    args.next_if(|arg| arg.is_some_and(|arg| arg.starts_with('+'))); // e.g. `cargo +nightly about`
+    let subcommand = args.next()??;
+    CARGO_SUBCOMMANDS.iter().find(|(name, _, _)| *name == subcommand)
+}
+
+const HINTS : &[(&str, &str)] = &[
+    ("cargo",           "install rust via https://rustup.rs/"),
+    ("rustc",           "install rust via https://rustup.rs/"),
+    ("rustup",          "install rustup via https://rustup.rs/"),
+    ("cargo-about",     "cargo install cargo-about"),
+    ("cargo-web",       "cargo install cargo-web"),
+    ("wasm-bindgen",    "cargo install wasm-bindgen-cli"),
+    ("wasm-pack",       "cargo install wasm-pack"),
+];
+
+/// (subcommand, hint if `cargo` is missing, hint if only the subcommand is missing)
+const CARGO_SUBCOMMANDS : &[(&str, &str, &str)] = &[
+    ("about",           "install rust via https://rustup.rs/, then: cargo install cargo-about", "cargo install cargo-about"),
+    ("web",             "install rust via https://rustup.rs/, then: cargo install cargo-web",   "cargo install cargo-web"),
+];
+
+#[cfg(windows)] fn candidates(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    let pathext = env::var_os("PATHEXT").unwrap_or_else(|| ".COM;.EXE;.BAT;.CMD".into());
+    let pathext = pathext.to_string_lossy().split(';').filter(|ext| !ext.is_empty()).map(String::from).collect::<Vec<_>>();
+    let has_ext = path.extension().is_some_and(|ext| pathext.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext.to_string_lossy())));
+    let exact = if has_ext { Some(path.to_path_buf()) } else { None };
+    exact.into_iter().chain(pathext.into_iter().map(move |ext| {
+        let mut path = std::ffi::OsString::from(path);
+        path.push(ext);
+        PathBuf::from(path)
+    }))
+}
+
+#[cfg(not(windows))] fn candidates(path: &Path) -> impl Iterator<Item = PathBuf> {
+    Some(path.to_path_buf()).into_iter()
+}
+
+#[cfg(unix)] fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))] fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}